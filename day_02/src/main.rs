@@ -1,13 +1,27 @@
 use core::panic;
-use std::{cmp::max, fs};
+use std::{
+    cmp::max,
+    env, fs,
+    io::{self, BufRead, BufReader, Read},
+    thread,
+    time::Duration,
+};
 
-fn solution(input_str: &str) -> (u32, u32) {
-    let mut s1: u32 = 0;
-    let mut s2: u32 = 0;
-    const RED: u32 = 12;
-    const GREEN: u32 = 13;
-    const BLUE: u32 = 14;
-    for (idx, line) in input_str.lines().enumerate() {
+const RED: u32 = 12;
+const GREEN: u32 = 13;
+const BLUE: u32 = 14;
+
+// Running totals for both parts, updated one game at a time
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct GameTotals {
+    games: usize,
+    s1: u32,
+    s2: u32,
+}
+
+impl GameTotals {
+    // Folds a single "Game N: ..." line into the running totals
+    fn add_game(&mut self, line: &str) {
         let mut max_red = 0;
         let mut max_green = 0;
         let mut max_blue = 0;
@@ -29,21 +43,118 @@ fn solution(input_str: &str) -> (u32, u32) {
             }
         }
 
+        self.games += 1;
+
         let adder: u32 = if RED >= max_red && GREEN >= max_green && BLUE >= max_blue {
-            (idx + 1) as u32
+            self.games as u32
         } else {
             0
         };
 
-        s1 += adder;
+        self.s1 += adder;
+
+        self.s2 += max_red * max_green * max_blue;
+    }
+
+    // Like `add_game`, but skips blank lines; returns whether the line was a game
+    // Both the whole-file and the streamed solutions go through here so they agree on input
+    fn add_line(&mut self, line: &str) -> bool {
+        let game = line.trim_end();
+        if game.is_empty() {
+            return false;
+        }
+        self.add_game(game);
+        true
+    }
+
+    fn answers(&self) -> (u32, u32) {
+        (self.s1, self.s2)
+    }
+}
+
+fn solution(input_str: &str) -> (u32, u32) {
+    let mut totals = GameTotals::default();
+    for line in input_str.lines() {
+        totals.add_line(line);
+    }
+    totals.answers()
+}
+
+// Reads games line by line, printing the running totals after every `every` games.
+// With `follow` set the reader is polled for new lines once it runs dry, so a file
+// that is still being written to can be tailed.
+fn stream_solution<R: Read>(reader: R, every: usize, follow: bool) -> (u32, u32) {
+    let mut reader = BufReader::new(reader);
+    let mut totals = GameTotals::default();
+    let mut line = String::new();
+    let mut pending = String::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .expect("Should have been able to read from the stream");
+        if read == 0 {
+            if follow {
+                thread::sleep(Duration::from_millis(250));
+                continue;
+            }
+            break;
+        }
 
-        s2 += max_red * max_green * max_blue;
+        // A partial line at the end of a growing file is held back until it is complete
+        pending.push_str(&line);
+        if follow && !pending.ends_with('\n') {
+            continue;
+        }
+        if totals.add_line(&pending) && totals.games % every == 0 {
+            println!("{} {} {}", totals.games, totals.s1, totals.s2);
+        }
+        pending.clear();
     }
-    (s1, s2)
+    totals.answers()
 }
 
 fn main() {
-    let file_path = "input.txt";
+    // Usage: day_02 [--stream [path|-]] [--every N] [--follow]
+    let mut stream = false;
+    let mut file_path = String::from("input.txt");
+    let mut every = 1;
+    let mut follow = false;
+
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stream" => {
+                stream = true;
+                file_path = match args.peek() {
+                    Some(p) if !p.starts_with("--") => args.next().expect(""),
+                    _ => String::from("-"),
+                };
+            }
+            "--every" => {
+                every = args
+                    .next()
+                    .expect("--every expects a number of games")
+                    .parse()
+                    .expect("--every expects a number of games");
+                assert!(every > 0, "--every must be at least 1");
+            }
+            "--follow" => follow = true,
+            x => panic!("Unexpected argument {}", x),
+        }
+    }
+
+    if stream {
+        let (p1, p2) = if file_path == "-" {
+            stream_solution(io::stdin().lock(), every, follow)
+        } else {
+            let file = fs::File::open(&file_path).expect("Should have been able to open the file");
+            stream_solution(file, every, follow)
+        };
+        println!("{p1}");
+        println!("{p2}");
+        return;
+    }
 
     let input_str = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
@@ -58,3 +169,65 @@ fn main() {
     let elapsed = now.elapsed();
     println!("Elapsed: {:.2?}", elapsed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+";
+
+    #[test]
+    fn totals_after_each_game() {
+        let mut totals = GameTotals::default();
+        let mut seen = vec![];
+        for line in EXAMPLE.lines() {
+            totals.add_game(line);
+            seen.push(totals.clone());
+        }
+        let expected = [
+            (1, 1, 48),
+            (2, 3, 60),
+            (3, 3, 1620),
+            (4, 3, 2250),
+            (5, 8, 2286),
+        ];
+        for (totals, (games, s1, s2)) in seen.iter().zip(expected) {
+            assert_eq!(totals, &GameTotals { games, s1, s2 });
+        }
+        assert_eq!(totals.answers(), (8, 2286));
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let mut totals = GameTotals::default();
+        assert!(!totals.add_line(""));
+        assert!(!totals.add_line("  \r\n"));
+        assert!(totals.add_line("Game 1: 3 blue, 4 red\r\n"));
+        assert_eq!(totals.games, 1);
+
+        let spaced = EXAMPLE.replace('\n', "\n\n");
+        assert_eq!(solution(&spaced), (8, 2286));
+        assert_eq!(stream_solution(spaced.as_bytes(), 1, false), (8, 2286));
+    }
+
+    #[test]
+    fn stream_matches_solution() {
+        assert_eq!(solution(EXAMPLE), (8, 2286));
+        for every in [1, 2, 10] {
+            assert_eq!(stream_solution(EXAMPLE.as_bytes(), every, false), (8, 2286));
+        }
+    }
+
+    #[test]
+    fn partial_last_line_still_counts_without_follow() {
+        let partial = EXAMPLE.trim_end();
+        assert!(!partial.ends_with('\n'));
+        assert_eq!(solution(partial), (8, 2286));
+        assert_eq!(stream_solution(partial.as_bytes(), 1, false), (8, 2286));
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    grid::{Adjacency, Grid, ParseError},
+    rng::Rng,
+    schematic::{GearRule, SchematicGraph},
+};
+
+// A schematic that keeps both answers up to date as single cells are edited
+// Each edit only rescans the cells neighbouring the edited cell and any
+// number that runs through it
+pub struct EditableSchematic {
+    grid: Grid<char>,
+    rule: GearRule,
+    adjacency: Adjacency,
+    part_sum: i64,
+    gear_sum: i64,
+}
+
+// (row, start, end) of a run of digits
+type Span = (usize, usize, usize);
+
+impl EditableSchematic {
+    pub fn new(
+        grid: Grid<char>,
+        rule: GearRule,
+        adjacency: Adjacency,
+    ) -> Result<EditableSchematic, ParseError> {
+        let graph = SchematicGraph::from_grid(&grid, &adjacency)?;
+        let part_sum = graph.part_number_sum();
+        let gear_sum = graph.gear_ratio_sum(&rule)?;
+        Ok(EditableSchematic {
+            grid,
+            rule,
+            adjacency,
+            part_sum,
+            gear_sum,
+        })
+    }
+
+    pub fn sums(&self) -> (i64, i64) {
+        (self.part_sum, self.gear_sum)
+    }
+
+    pub fn set_digit(&mut self, x: usize, y: usize, digit: u32) -> Result<(), ParseError> {
+        let c = char::from_digit(digit, 10).ok_or_else(|| self.error(x, y, "not a digit"))?;
+        self.set(x, y, c)
+    }
+
+    pub fn place_symbol(&mut self, x: usize, y: usize, symbol: char) -> Result<(), ParseError> {
+        if symbol.is_ascii_digit() || symbol == '.' {
+            return Err(self.error(x, y, &format!("{:?} is not a symbol", symbol)));
+        }
+        self.set(x, y, symbol)
+    }
+
+    pub fn remove(&mut self, x: usize, y: usize) -> Result<(), ParseError> {
+        self.set(x, y, '.')
+    }
+
+    // A digit sets a digit, '.' clears the cell and anything else is placed as a symbol
+    pub fn apply(&mut self, x: usize, y: usize, c: char) -> Result<(), ParseError> {
+        match c {
+            '0'..='9' => self.set_digit(x, y, c.to_digit(10).expect("")),
+            '.' => self.remove(x, y),
+            symbol => self.place_symbol(x, y, symbol),
+        }
+    }
+
+    // Both answers worked out again from scratch, for checking the incremental ones
+    pub fn recompute(&self) -> Result<(i64, i64), ParseError> {
+        let graph = SchematicGraph::from_grid(&self.grid, &self.adjacency)?;
+        Ok((graph.part_number_sum(), graph.gear_ratio_sum(&self.rule)?))
+    }
+
+    fn set(&mut self, x: usize, y: usize, c: char) -> Result<(), ParseError> {
+        if self.grid.get(x, y).is_none() {
+            return Err(self.error(x, y, "outside the schematic"));
+        }
+        if c.is_whitespace() || c.is_control() {
+            return Err(self.error(x, y, &format!("unexpected character {:?}", c)));
+        }
+
+        // Only (x, y) changes, so the digit runs either side of it are the same before and after
+        let row = self.grid.row(y);
+        let mut lo = x;
+        while lo > 0 && row[lo - 1].is_ascii_digit() {
+            lo -= 1;
+        }
+        let mut hi = x;
+        while hi + 1 < row.len() && row[hi + 1].is_ascii_digit() {
+            hi += 1;
+        }
+
+        // The run through (x, y) may merge or split, and (x, y) itself may gain or lose a symbol
+        // Only numbers and symbols neighbouring those cells can change what they contribute
+        let mut region: HashSet<(usize, usize)> = HashSet::new();
+        for xp in lo..=hi {
+            region.insert((xp, y));
+            region.extend(self.grid.neighbours(xp, y, &self.adjacency));
+        }
+
+        let before = self.contribution(&region)?;
+        let old = self.grid[(x, y)];
+        self.grid[(x, y)] = c;
+        // A number that no longer fits, or a gear ratio or total that overflows, rejects the edit
+        let sums = self.contribution(&region).and_then(|after| {
+            let gear_sum = (self.gear_sum - before.1)
+                .checked_add(after.1)
+                .ok_or_else(|| GearRule::overflow(x, y))?;
+            Ok((self.part_sum - before.0 + after.0, gear_sum))
+        });
+        match sums {
+            Ok((part_sum, gear_sum)) => {
+                self.part_sum = part_sum;
+                self.gear_sum = gear_sum;
+                Ok(())
+            }
+            Err(e) => {
+                self.grid[(x, y)] = old;
+                Err(e)
+            }
+        }
+    }
+
+    // Part numbers that reach into the region, and gear ratios of symbols inside it
+    // An edit can only change these, so their difference is the change in each answer
+    fn contribution(&self, region: &HashSet<(usize, usize)>) -> Result<(i64, i64), ParseError> {
+        let mut part_sum = 0;
+        for span in self.local_numbers(region)? {
+            if self.is_part(span) {
+                part_sum += self.value(span) as i64;
+            }
+        }
+
+        let mut gear_sum: i64 = 0;
+        for &(x, y) in region {
+            let c = self.grid[(x, y)];
+            if c.is_ascii_digit() || c == '.' {
+                continue;
+            }
+            let touching: HashSet<Span> = self
+                .grid
+                .neighbours(x, y, &self.adjacency)
+                .into_iter()
+                .filter(|&p| self.grid[p].is_ascii_digit())
+                .map(|(xp, yp)| self.span_at(xp, yp))
+                .collect();
+            if self.rule.matches(c, touching.len()) {
+                gear_sum = self
+                    .rule
+                    .aggregate(touching.iter().map(|&s| self.value(s)))
+                    .and_then(|ratio| gear_sum.checked_add(ratio))
+                    .ok_or_else(|| GearRule::overflow(x, y))?;
+            }
+        }
+
+        Ok((part_sum, gear_sum))
+    }
+
+    // Every number with a digit inside the region, checking each one fits in an i32
+    fn local_numbers(&self, region: &HashSet<(usize, usize)>) -> Result<HashSet<Span>, ParseError> {
+        let spans: HashSet<Span> = region
+            .iter()
+            .filter(|&&p| self.grid[p].is_ascii_digit())
+            .map(|&(x, y)| self.span_at(x, y))
+            .collect();
+        for &(y, start, end) in &spans {
+            let digits: String = self.grid.row(y)[start..end].iter().collect();
+            if digits.parse::<i32>().is_err() {
+                return Err(self.error(start, y, &format!("number {} is too large", digits)));
+            }
+        }
+        Ok(spans)
+    }
+
+    fn span_at(&self, x: usize, y: usize) -> Span {
+        let row = self.grid.row(y);
+        let start = row[..x]
+            .iter()
+            .rposition(|c| !c.is_ascii_digit())
+            .map_or(0, |p| p + 1);
+        let end = row[x..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .map_or(row.len(), |p| x + p);
+        (y, start, end)
+    }
+
+    fn value(&self, (y, start, end): Span) -> i32 {
+        self.grid.row(y)[start..end]
+            .iter()
+            .fold(0, |v, c| v * 10 + c.to_digit(10).expect("") as i32)
+    }
+
+    fn is_part(&self, (y, start, end): Span) -> bool {
+        (start..end).any(|x| {
+            self.grid
+                .neighbours(x, y, &self.adjacency)
+                .into_iter()
+                .any(|p| !self.grid[p].is_ascii_digit() && self.grid[p] != '.')
+        })
+    }
+
+    fn error(&self, x: usize, y: usize, message: &str) -> ParseError {
+        ParseError {
+            line: y + 1,
+            column: x + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+// An edit to a random cell: a digit half the time, otherwise clearing it or placing a symbol
+pub fn random_edit(rng: &mut Rng, width: usize, height: usize) -> (usize, usize, char) {
+    let (x, y) = (rng.below(width), rng.below(height));
+    let c = match rng.below(10) {
+        0..=4 => char::from_digit(rng.below(10) as u32, 10).expect(""),
+        5..=7 => '.',
+        _ => ['*', '#', '+', '$'][rng.below(4)],
+    };
+    (x, y, c)
+}
+
+// Applies `count` random edits, returning the mean, 99th percentile and slowest time per edit
+// Edits the schematic rejects are timed too; the final answers are checked against a full
+// recompute
+pub fn bench_edits(
+    editable: &mut EditableSchematic,
+    count: usize,
+    seed: u64,
+) -> (Duration, Duration, Duration) {
+    let mut rng = Rng::new(seed);
+    let (width, height) = (editable.grid.width(), editable.grid.height());
+    let mut times: Vec<Duration> = (0..count.max(1))
+        .map(|_| {
+            let (x, y, c) = random_edit(&mut rng, width, height);
+            let now = Instant::now();
+            let _ = editable.apply(x, y, c);
+            now.elapsed()
+        })
+        .collect();
+    assert_eq!(
+        Ok(editable.sums()),
+        editable.recompute(),
+        "Incremental sums drifted from a full recompute"
+    );
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    (mean, times[times.len() * 99 / 100], times[times.len() - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grid::Neighbourhood,
+        schematic::{parse_grid, Aggregation, NeighbourCount},
+    };
+
+    fn random_grid(rng: &mut Rng, width: usize, height: usize) -> Grid<char> {
+        let mut grid = Grid::new(width, height, '.');
+        for _ in 0..width * height / 2 {
+            let (x, y, c) = random_edit(rng, width, height);
+            grid[(x, y)] = c;
+        }
+        grid
+    }
+
+    #[test]
+    fn an_edit_that_overflows_is_rejected() {
+        let grid = parse_grid(
+            "999999999.999999999\n...................\n999999999.999999999\n",
+            false,
+        )
+        .expect("valid");
+        let rule = GearRule {
+            count: NeighbourCount::Exactly(4),
+            ..GearRule::default()
+        };
+        let mut editable = EditableSchematic::new(grid, rule, Adjacency::default()).expect("valid");
+        assert_eq!(editable.sums(), (0, 0));
+        assert_eq!(
+            editable.place_symbol(9, 1, '*'),
+            Err(GearRule::overflow(9, 1))
+        );
+        assert_eq!(editable.sums(), (0, 0));
+        // The rejected symbol isn't left behind
+        assert_eq!(editable.place_symbol(9, 1, '#'), Ok(()));
+        assert_eq!(editable.sums(), (3999999996, 0));
+    }
+
+    #[test]
+    fn edits_match_a_full_recompute() {
+        let neighbourhoods = [
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Chebyshev(1),
+            Neighbourhood::Chebyshev(2),
+        ];
+        let rules = [
+            GearRule::default(),
+            GearRule {
+                symbols: vec!['*', '#'],
+                count: NeighbourCount::AtLeast(1),
+                aggregation: Aggregation::Sum,
+            },
+            GearRule {
+                symbols: vec!['+'],
+                count: NeighbourCount::AtLeast(2),
+                aggregation: Aggregation::Max,
+            },
+        ];
+        let mut rng = Rng::new(5);
+        for round in 0..300 {
+            // Small enough that wrapped neighbourhoods often reach the same cell twice
+            let (width, height) = (1 + rng.below(8), 1 + rng.below(6));
+            let adjacency = Adjacency {
+                neighbourhood: neighbourhoods[round % 3],
+                wrap: round % 2 == 1,
+            };
+            let rule = rules[rng.below(rules.len())].clone();
+            let grid = random_grid(&mut rng, width, height);
+            let Ok(mut editable) = EditableSchematic::new(grid, rule, adjacency) else {
+                continue;
+            };
+            for _ in 0..40 {
+                let (x, y, c) = random_edit(&mut rng, width, height);
+                let before = editable.grid.clone();
+                let result = editable.apply(x, y, c);
+                if result.is_err() {
+                    assert_eq!(editable.grid, before, "a rejected edit changed the grid");
+                }
+                assert_eq!(
+                    Ok(editable.sums()),
+                    editable.recompute(),
+                    "after {},{},{} under {:?} with {:?} on\n{:?}",
+                    x,
+                    y,
+                    c,
+                    adjacency,
+                    editable.rule,
+                    editable.grid
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    cmp::min,
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+const OFFSETS_4: [(i64, i64); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const OFFSETS_8: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Neighbourhood {
+    // Up, down, left and right only
+    VonNeumann,
+    // Every cell within Chebyshev distance k, so Chebyshev(1) is the usual 8-neighbourhood
+    Chebyshev(usize),
+}
+
+// Which cells count as neighbours, and whether the grid's edges wrap around
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Adjacency {
+    pub neighbourhood: Neighbourhood,
+    pub wrap: bool,
+}
+
+impl Default for Adjacency {
+    fn default() -> Adjacency {
+        Adjacency {
+            neighbourhood: Neighbourhood::Chebyshev(1),
+            wrap: false,
+        }
+    }
+}
+
+impl Neighbourhood {
+    // How many rows or columns away a neighbour can be
+    pub fn radius(&self) -> usize {
+        match self {
+            Neighbourhood::VonNeumann => 1,
+            Neighbourhood::Chebyshev(k) => *k,
+        }
+    }
+}
+
+impl Adjacency {
+    fn offsets(&self) -> Vec<(i64, i64)> {
+        match self.neighbourhood {
+            Neighbourhood::VonNeumann => OFFSETS_4.to_vec(),
+            Neighbourhood::Chebyshev(k) => {
+                let k = k as i64;
+                (-k..=k)
+                    .flat_map(|dy| (-k..=k).map(move |dx| (dx, dy)))
+                    .filter(|&d| d != (0, 0))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Adjacency {
+    // Whether two different cells of a width x height grid are neighbours, straight from the
+    // definition, for checking the faster ways of finding them
+    pub fn reaches(
+        &self,
+        a: (usize, usize),
+        b: (usize, usize),
+        width: usize,
+        height: usize,
+    ) -> bool {
+        let axis = |p: usize, q: usize, size: usize| {
+            let d = p.abs_diff(q);
+            if self.wrap {
+                d.min(size - d)
+            } else {
+                d
+            }
+        };
+        let (dx, dy) = (axis(a.0, b.0, width), axis(a.1, b.1, height));
+        let close = match self.neighbourhood {
+            Neighbourhood::VonNeumann => dx + dy == 1,
+            Neighbourhood::Chebyshev(k) => dx.max(dy) <= k,
+        };
+        close && a != b
+    }
+}
+
+// A rectangular grid stored row-major, indexed by (x, y)
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    // Builds a grid from text, one row per line, mapping each char through `f`
+    // Every line must be the same length as the first one
+    pub fn parse_with(input: &str, f: impl Fn(char) -> T) -> Result<Grid<T>, ParseError> {
+        let rows = split_rows(input);
+        let width = rows.first().map_or(0, |r| r.chars().count());
+        let mut cells = Vec::with_capacity(width * rows.len());
+        for (y, line) in rows.iter().enumerate() {
+            let row_width = line.chars().count();
+            if row_width != width {
+                return Err(ParseError {
+                    line: y + 1,
+                    column: min(row_width, width) + 1,
+                    message: format!(
+                        "line is {} characters wide but the first line is {}",
+                        row_width, width
+                    ),
+                });
+            }
+            cells.extend(line.chars().map(&f));
+        }
+
+        Ok(Grid {
+            width,
+            height: rows.len(),
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x < self.width && y < self.height {
+            Some(&self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if x < self.width && y < self.height {
+            Some(&mut self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks() panics on a zero chunk size, so an empty-width grid yields no rows
+        self.cells.chunks(self.width.max(1))
+    }
+
+    // Column access is part of the shared grid API, though day_03 itself only reads rows
+    #[allow(dead_code)]
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width, "Column {} is outside the grid", x);
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    #[allow(dead_code)]
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(|x| self.column(x))
+    }
+
+    // The in-bounds up/down/left/right neighbours of (x, y)
+    pub fn neighbours4(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.offset_positions(x, y, &OFFSETS_4)
+    }
+
+    // The in-bounds neighbours of (x, y) including diagonals
+    pub fn neighbours8(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.offset_positions(x, y, &OFFSETS_8)
+    }
+
+    // The neighbours of (x, y) under the given adjacency model
+    // With wrapping, small grids can reach the same cell more than once; it is only listed once
+    pub fn neighbours(&self, x: usize, y: usize, adjacency: &Adjacency) -> Vec<(usize, usize)> {
+        if !adjacency.wrap {
+            return match adjacency.neighbourhood {
+                Neighbourhood::VonNeumann => self.neighbours4(x, y).collect(),
+                Neighbourhood::Chebyshev(1) => self.neighbours8(x, y).collect(),
+                Neighbourhood::Chebyshev(_) => {
+                    self.offset_positions(x, y, &adjacency.offsets()).collect()
+                }
+            };
+        }
+
+        let offsets = adjacency.offsets();
+
+        let (w, h) = (self.width as i64, self.height as i64);
+        let mut out: Vec<(usize, usize)> = offsets
+            .iter()
+            .map(|(dx, dy)| {
+                (
+                    (x as i64 + dx).rem_euclid(w) as usize,
+                    (y as i64 + dy).rem_euclid(h) as usize,
+                )
+            })
+            .filter(|&p| p != (x, y))
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    fn offset_positions<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        offsets: &'a [(i64, i64)],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        offsets.iter().filter_map(move |(dx, dy)| {
+            let xp = x as i64 + dx;
+            let yp = y as i64 + dy;
+            if self.contains(xp, yp) {
+                Some((xp as usize, yp as usize))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    // Like parse_with, but lines may differ in length
+    // Short rows are padded with `pad` out to the longest line
+    pub fn parse_ragged_with(input: &str, pad: T, f: impl Fn(char) -> T) -> Grid<T> {
+        let rows = split_rows(input);
+        let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * rows.len());
+        for line in &rows {
+            let row_start = cells.len();
+            cells.extend(line.chars().map(&f));
+            cells.resize(row_start + width, pad.clone());
+        }
+
+        Grid {
+            width,
+            height: rows.len(),
+            cells,
+        }
+    }
+}
+
+impl Grid<char> {
+    pub fn parse(input: &str) -> Result<Grid<char>, ParseError> {
+        Grid::parse_with(input, |c| c)
+    }
+
+    pub fn parse_ragged(input: &str, pad: char) -> Grid<char> {
+        Grid::parse_ragged_with(input, pad, |c| c)
+    }
+}
+
+// Where and why some text could not be turned into a grid
+// Lines and columns are 1-based to match what an editor shows
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+// Splits text into rows, dropping the '\r' of CRLF endings and any blank lines at the end
+fn split_rows(input: &str) -> Vec<&str> {
+    let mut rows: Vec<&str> = input
+        .split('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .collect();
+    while rows.last().is_some_and(|r| r.is_empty()) {
+        rows.pop();
+    }
+    rows
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        self.get(x, y)
+            .unwrap_or_else(|| panic!("({}, {}) is outside the grid", x, y))
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        self.get_mut(x, y)
+            .unwrap_or_else(|| panic!("({}, {}) is outside the grid", x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every other cell close enough under the adjacency, by checking all of them
+    fn brute_force(
+        grid: &Grid<char>,
+        x: usize,
+        y: usize,
+        adjacency: &Adjacency,
+    ) -> Vec<(usize, usize)> {
+        let (width, height) = (grid.width(), grid.height());
+        let mut out: Vec<(usize, usize)> = (0..height)
+            .flat_map(|yp| (0..width).map(move |xp| (xp, yp)))
+            .filter(|&p| adjacency.reaches((x, y), p, width, height))
+            .collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn neighbours_are_the_cells_within_range() {
+        // Includes grids narrower and shorter than 2k + 1, where wrapping reaches a cell
+        // from both sides and it must still only be listed once
+        let neighbourhoods = [
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Chebyshev(1),
+            Neighbourhood::Chebyshev(2),
+            Neighbourhood::Chebyshev(3),
+        ];
+        for (width, height) in (1..7).flat_map(|w| (1..6).map(move |h| (w, h))) {
+            let grid = Grid::new(width, height, '.');
+            for neighbourhood in neighbourhoods {
+                for wrap in [false, true] {
+                    let adjacency = Adjacency {
+                        neighbourhood,
+                        wrap,
+                    };
+                    for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                        let mut found = grid.neighbours(x, y, &adjacency);
+                        found.sort();
+                        assert_eq!(
+                            found,
+                            brute_force(&grid, x, y, &adjacency),
+                            "{:?} at ({}, {}) on {}x{}",
+                            adjacency,
+                            x,
+                            y,
+                            width,
+                            height
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
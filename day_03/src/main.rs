@@ -1,93 +1,263 @@
-use std::{collections::HashSet, fs, vec};
+mod edit;
+mod grid;
+mod render;
+mod rng;
+mod schematic;
+mod stream;
 
-fn solution(input_str: &str) -> (i32, i32) {
-    let height = input_str.lines().count();
-    let width = input_str
-        .lines()
-        .next()
-        .expect("String has no lines")
-        .chars()
-        .count();
+use std::{env, fs, io::BufReader};
 
-    let mut schem_vector: Vec<i32> = vec![-1; height * width];
-    let mut num_index = 0;
-    let mut num_vector: Vec<i32> = vec![];
+use edit::{bench_edits, EditableSchematic};
+use grid::{Adjacency, Neighbourhood, ParseError};
+use render::{render_ansi, render_html};
+use schematic::{parse_grid, Aggregation, GearRule, NeighbourCount, SchematicGraph};
+use stream::stream_solution;
 
-    for (y, line) in input_str.lines().enumerate() {
-        let mut curr_num = String::new();
-        for (x, c) in line.chars().enumerate() {
-            match c {
-                '0'..='9' => {
-                    curr_num.push(c);
-                    schem_vector[y * width + x] = num_index;
-                }
-                _ => {
-                    if !curr_num.is_empty() {
-                        num_vector.push(curr_num.parse::<i32>().expect(""));
-                        num_index += 1;
-                        curr_num = String::new();
+fn solution(
+    input_str: &str,
+    rule: &GearRule,
+    adjacency: &Adjacency,
+    ragged: bool,
+) -> Result<(i64, i64), ParseError> {
+    let graph = SchematicGraph::parse(input_str, ragged, adjacency)?;
+    Ok((graph.part_number_sum(), graph.gear_ratio_sum(rule)?))
+}
+
+enum Query {
+    Dot(String),
+    Json(String),
+    Touching(char),
+    Neighbours(usize),
+    Isolated,
+    Ansi,
+    Html(String),
+}
+
+fn main() {
+    // Usage: day_03 [--dot path] [--json path] [--touching symbol] [--neighbours n] [--isolated]
+    //               [--gear-symbols chars] [--gear-count n|n+] [--gear-agg product|sum|max]
+    //               [--adjacency 4|8|radius:k] [--wrap] [--ragged] [--stream]
+    //               [--ansi] [--html path] [--edit x,y,c ...]
+    //        day_03 [--adjacency ...] --bench-edits count [--seed n]
+    let file_path = "input.txt";
+
+    let mut rule = GearRule::default();
+    let mut adjacency = Adjacency::default();
+    let mut ragged = false;
+    let mut stream = false;
+    let mut queries = vec![];
+    let mut edits = vec![];
+    let mut bench = None;
+    let mut seed = 1;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => queries.push(Query::Dot(
+                args.next().expect("--dot expects an output path"),
+            )),
+            "--json" => queries.push(Query::Json(
+                args.next().expect("--json expects an output path"),
+            )),
+            "--touching" => queries.push(Query::Touching(
+                args.next()
+                    .and_then(|s| s.chars().next())
+                    .expect("--touching expects a symbol"),
+            )),
+            "--neighbours" => queries.push(Query::Neighbours(
+                args.next()
+                    .expect("--neighbours expects a count")
+                    .parse()
+                    .expect("--neighbours expects a count"),
+            )),
+            "--isolated" => queries.push(Query::Isolated),
+            "--ansi" => queries.push(Query::Ansi),
+            "--html" => queries.push(Query::Html(
+                args.next().expect("--html expects an output path"),
+            )),
+            "--gear-symbols" => {
+                rule.symbols = args
+                    .next()
+                    .expect("--gear-symbols expects a list of symbols")
+                    .chars()
+                    .collect();
+            }
+            "--gear-count" => {
+                let count = args.next().expect("--gear-count expects n or n+");
+                rule.count = match count.strip_suffix('+') {
+                    Some(n) => {
+                        NeighbourCount::AtLeast(n.parse().expect("--gear-count expects n or n+"))
                     }
-                }
+                    None => NeighbourCount::Exactly(
+                        count.parse().expect("--gear-count expects n or n+"),
+                    ),
+                };
             }
-        }
-        if !curr_num.is_empty() {
-            num_vector.push(curr_num.parse::<i32>().expect(""));
-            num_index += 1;
+            "--gear-agg" => {
+                rule.aggregation = match args.next().as_deref() {
+                    Some("product") => Aggregation::Product,
+                    Some("sum") => Aggregation::Sum,
+                    Some("max") => Aggregation::Max,
+                    _ => panic!("--gear-agg expects product, sum or max"),
+                };
+            }
+            "--edit" => {
+                let edit = args.next().expect("--edit expects x,y,c");
+                let parts: Vec<&str> = edit.splitn(3, ',').collect();
+                assert_eq!(parts.len(), 3, "--edit expects x,y,c");
+                edits.push((
+                    parts[0].parse::<usize>().expect("--edit expects x,y,c"),
+                    parts[1].parse::<usize>().expect("--edit expects x,y,c"),
+                    parts[2].chars().next().expect("--edit expects x,y,c"),
+                ));
+            }
+            "--bench-edits" => {
+                bench = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .expect("--bench-edits expects a number of edits"),
+                );
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--seed expects a number");
+            }
+            "--adjacency" => {
+                adjacency.neighbourhood = match args.next().as_deref() {
+                    Some("4") => Neighbourhood::VonNeumann,
+                    Some("8") => Neighbourhood::Chebyshev(1),
+                    k => Neighbourhood::Chebyshev(
+                        k.and_then(|k| k.strip_prefix("radius:"))
+                            .and_then(|k| k.parse().ok())
+                            .expect("--adjacency expects 4, 8 or radius:k"),
+                    ),
+                };
+            }
+            "--wrap" => adjacency.wrap = true,
+            "--ragged" => ragged = true,
+            "--stream" => stream = true,
+            x => panic!("Unexpected argument {}", x),
         }
     }
 
-    let mut indices_to_add: HashSet<i32> = HashSet::new();
-    let mut sump2 = 0;
+    // Wrap-around joins the last row to the first, which a stream only reaches at the end
+    if stream && adjacency.wrap {
+        panic!("--stream can't be combined with --wrap");
+    }
 
-    for (y, line) in input_str.lines().enumerate() {
-        for (x, ch) in line.chars().enumerate() {
-            match ch {
-                '0'..='9' | '.' => {
-                    continue;
+    // Streaming reads the schematic three rows at a time instead of loading it all
+    if stream {
+        let file = fs::File::open(file_path).expect("Should have been able to open the file");
+        let (p1, p2) = stream_solution(
+            BufReader::new(file),
+            &rule,
+            &adjacency.neighbourhood,
+            ragged,
+        )
+        .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        println!("{p1}");
+        println!("{p2}");
+        return;
+    }
+
+    let input_str = fs::read_to_string(file_path).expect("Should have been able to read the file");
+
+    // Times random single-cell edits, to check they stay well under a millisecond
+    if let Some(count) = bench {
+        let schematic =
+            parse_grid(&input_str, ragged).unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        let mut editable = EditableSchematic::new(schematic, rule, adjacency)
+            .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        let (mean, p99, slowest) = bench_edits(&mut editable, count, seed);
+        println!(
+            "{} edits: mean {:.2?}, 99th percentile {:.2?}, slowest {:.2?}",
+            count, mean, p99, slowest
+        );
+        return;
+    }
+
+    // Apply each edit in turn, printing both answers after each one
+    if !edits.is_empty() {
+        let schematic =
+            parse_grid(&input_str, ragged).unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        let mut editable = EditableSchematic::new(schematic, rule, adjacency)
+            .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        for (x, y, c) in edits {
+            use std::time::Instant;
+            let now = Instant::now();
+            let result = editable.apply(x, y, c);
+            let elapsed = now.elapsed();
+            match result {
+                Ok(()) => {
+                    let (p1, p2) = editable.sums();
+                    println!("{},{},{}: {} {} ({:.2?})", x, y, c, p1, p2, elapsed);
                 }
-                symbol => {
-                    let mut new_indices = HashSet::new();
-                    for i in -1..=1 {
-                        let yp = y as i32 + i;
-                        if yp >= height as i32 || yp < 0 {
-                            continue;
-                        }
-                        for j in -1..=1 {
-                            let xp = x as i32 + j;
-                            if xp >= width as i32 || xp < 0 {
-                                continue;
-                            }
-                            let v = schem_vector[(yp * width as i32 + xp) as usize];
-                            if v >= 0 {
-                                new_indices.insert(v);
-                            }
-                        }
+                Err(e) => println!("{},{},{}: rejected at {}", x, y, c, e),
+            }
+        }
+        return;
+    }
+
+    if !queries.is_empty() {
+        let schematic =
+            parse_grid(&input_str, ragged).unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        let graph = SchematicGraph::from_grid(&schematic, &adjacency)
+            .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+        for query in queries {
+            match query {
+                Query::Dot(path) => {
+                    fs::write(path, graph.to_dot())
+                        .expect("Should have been able to write the file");
+                }
+                Query::Json(path) => {
+                    fs::write(path, graph.to_json())
+                        .expect("Should have been able to write the file");
+                }
+                Query::Touching(symbol) => {
+                    for n in graph.numbers_touching(symbol) {
+                        println!(
+                            "{} at row {}, columns {}..{}",
+                            n.value, n.row, n.start, n.end
+                        );
                     }
-                    if symbol == '*' && new_indices.len() == 2 {
-                        let values = new_indices.iter().map(|&i| num_vector[i as usize]);
-                        sump2 += values.product::<i32>();
+                }
+                Query::Neighbours(count) => {
+                    for s_idx in graph.symbols_with_neighbours(count) {
+                        let s = &graph.symbols[s_idx];
+                        let values: Vec<i32> = graph.numbers_of(s_idx).map(|n| n.value).collect();
+                        println!("{} at ({}, {}): {:?}", s.symbol, s.x, s.y, values);
                     }
-
-                    indices_to_add.extend(new_indices);
+                }
+                Query::Isolated => {
+                    for n in graph.isolated_numbers() {
+                        println!(
+                            "{} at row {}, columns {}..{}",
+                            n.value, n.row, n.start, n.end
+                        );
+                    }
+                }
+                Query::Ansi => print!(
+                    "{}",
+                    render_ansi(&schematic, &graph, &rule)
+                        .unwrap_or_else(|e| panic!("Invalid schematic at {}", e))
+                ),
+                Query::Html(path) => {
+                    let html = render_html(&schematic, &graph, &rule)
+                        .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
+                    fs::write(path, html).expect("Should have been able to write the file");
                 }
             }
         }
+        return;
     }
 
-    let sump1: i32 = indices_to_add.iter().map(|&i| num_vector[i as usize]).sum();
-
-    (sump1, sump2)
-}
-
-fn main() {
-    let file_path = "input.txt";
-
-    let input_str = fs::read_to_string(file_path).expect("Should have been able to read the file");
-
     use std::time::Instant;
     let now = Instant::now();
     for _ in 0..1 {
-        let (p1, p2) = solution(&input_str);
+        let (p1, p2) = solution(&input_str, &rule, &adjacency, ragged)
+            .unwrap_or_else(|e| panic!("Invalid schematic at {}", e));
         println!("{p1}");
         println!("{p2}");
     }
@@ -0,0 +1,152 @@
+use std::fmt::Write;
+
+use crate::{
+    grid::{Grid, ParseError},
+    schematic::{GearRule, SchematicGraph},
+};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Cell {
+    Empty,
+    PartNumber,
+    OtherNumber,
+    Symbol,
+    // Holds the gear's ratio
+    Gear(i64),
+}
+
+impl Cell {
+    fn ansi_colour(&self) -> Option<&'static str> {
+        match self {
+            Cell::Empty => None,
+            Cell::PartNumber => Some("\x1b[1;32m"),
+            Cell::OtherNumber => Some("\x1b[2;37m"),
+            Cell::Symbol => Some("\x1b[1;34m"),
+            Cell::Gear(_) => Some("\x1b[1;33m"),
+        }
+    }
+
+    fn css_class(&self) -> Option<&'static str> {
+        match self {
+            Cell::Empty => None,
+            Cell::PartNumber => Some("part"),
+            Cell::OtherNumber => Some("other"),
+            Cell::Symbol => Some("symbol"),
+            Cell::Gear(_) => Some("gear"),
+        }
+    }
+}
+
+// Works out how every cell should be drawn from the adjacency already in the graph
+fn classify(
+    schematic: &Grid<char>,
+    graph: &SchematicGraph,
+    rule: &GearRule,
+) -> Result<Grid<Cell>, ParseError> {
+    let mut cells = Grid::new(schematic.width(), schematic.height(), Cell::Empty);
+    for (n_idx, n) in graph.numbers.iter().enumerate() {
+        let cell = if graph.is_part_number(n_idx) {
+            Cell::PartNumber
+        } else {
+            Cell::OtherNumber
+        };
+        for x in n.start..n.end {
+            cells[(x, n.row)] = cell;
+        }
+    }
+    for (s_idx, s) in graph.symbols.iter().enumerate() {
+        cells[(s.x, s.y)] = match graph.gear_ratio(s_idx, rule)? {
+            Some(ratio) => Cell::Gear(ratio),
+            None => Cell::Symbol,
+        };
+    }
+    Ok(cells)
+}
+
+// Part numbers in green, other numbers dimmed, symbols in blue and gears in yellow
+pub fn render_ansi(
+    schematic: &Grid<char>,
+    graph: &SchematicGraph,
+    rule: &GearRule,
+) -> Result<String, ParseError> {
+    let cells = classify(schematic, graph, rule)?;
+    let mut out = String::new();
+    for (row, cell_row) in schematic.rows().zip(cells.rows()) {
+        let mut current = None;
+        for (&c, cell) in row.iter().zip(cell_row) {
+            let colour = cell.ansi_colour();
+            if colour != current {
+                out.push_str(colour.unwrap_or("\x1b[0m"));
+                current = colour;
+            }
+            out.push(c);
+        }
+        if current.is_some() {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// A standalone page with the same colouring; hovering a gear shows its ratio
+pub fn render_html(
+    schematic: &Grid<char>,
+    graph: &SchematicGraph,
+    rule: &GearRule,
+) -> Result<String, ParseError> {
+    let cells = classify(schematic, graph, rule)?;
+    let mut out = String::from(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Schematic</title>
+<style>
+body { background: #111; color: #555; }
+pre { font-family: monospace; line-height: 1.1; }
+.part { color: #4c4; font-weight: bold; }
+.other { color: #888; }
+.symbol { color: #58f; font-weight: bold; }
+.gear { color: #fc3; font-weight: bold; cursor: help; }
+.gear:hover { background: #fc3; color: #111; }
+</style>
+</head>
+<body>
+<pre>",
+    );
+    for (row, cell_row) in schematic.rows().zip(cells.rows()) {
+        let mut current = None;
+        for (&c, &cell) in row.iter().zip(cell_row) {
+            // Each gear gets its own span so it can carry its own tooltip
+            if current != Some(cell) || matches!(cell, Cell::Gear(_)) {
+                if current.and_then(|c: Cell| c.css_class()).is_some() {
+                    out.push_str("</span>");
+                }
+                match cell {
+                    Cell::Gear(ratio) => {
+                        write!(out, "<span class=\"gear\" title=\"ratio: {}\">", ratio).expect("")
+                    }
+                    cell => {
+                        if let Some(class) = cell.css_class() {
+                            write!(out, "<span class=\"{}\">", class).expect("");
+                        }
+                    }
+                }
+                current = Some(cell);
+            }
+            match c {
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '&' => out.push_str("&amp;"),
+                c => out.push(c),
+            }
+        }
+        if current.and_then(|c| c.css_class()).is_some() {
+            out.push_str("</span>");
+        }
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</body>\n</html>\n");
+    Ok(out)
+}
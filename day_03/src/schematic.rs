@@ -0,0 +1,620 @@
+use std::fmt::Write;
+
+use crate::grid::{Adjacency, Grid, Neighbourhood, ParseError};
+
+// A run of digits on a single row, covering columns start..end
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Number {
+    pub value: i32,
+    pub row: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Symbol {
+    pub symbol: char,
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NeighbourCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Aggregation {
+    Product,
+    Sum,
+    Max,
+}
+
+// Which symbols count as gears, how many adjacent numbers they need,
+// and how those numbers combine into a ratio
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GearRule {
+    pub symbols: Vec<char>,
+    pub count: NeighbourCount,
+    pub aggregation: Aggregation,
+}
+
+impl Default for GearRule {
+    // The puzzle's rule: a '*' next to exactly two numbers, multiplied together
+    fn default() -> GearRule {
+        GearRule {
+            symbols: vec!['*'],
+            count: NeighbourCount::Exactly(2),
+            aggregation: Aggregation::Product,
+        }
+    }
+}
+
+impl GearRule {
+    pub fn matches(&self, symbol: char, neighbours: usize) -> bool {
+        let count_ok = match self.count {
+            NeighbourCount::Exactly(n) => neighbours == n,
+            NeighbourCount::AtLeast(n) => neighbours >= n,
+        };
+        count_ok && self.symbols.contains(&symbol)
+    }
+
+    // None if the result doesn't fit in an i64, which a product of a few numbers can easily do
+    pub fn aggregate(&self, mut values: impl Iterator<Item = i32>) -> Option<i64> {
+        match self.aggregation {
+            Aggregation::Product => values.try_fold(1i64, |acc, v| acc.checked_mul(v as i64)),
+            Aggregation::Sum => values.try_fold(0i64, |acc, v| acc.checked_add(v as i64)),
+            Aggregation::Max => Some(values.max().map_or(0, |v| v as i64)),
+        }
+    }
+
+    // The error for a gear at (x, y) whose ratio, or the total it is added to, overflows
+    pub fn overflow(x: usize, y: usize) -> ParseError {
+        ParseError {
+            line: y + 1,
+            column: x + 1,
+            message: String::from("gear ratio doesn't fit in an i64"),
+        }
+    }
+}
+
+// Bipartite graph of numbers and symbols, with an edge wherever a symbol
+// neighbours any digit of a number under the chosen adjacency model
+#[derive(Debug, Clone)]
+pub struct SchematicGraph {
+    pub numbers: Vec<Number>,
+    pub symbols: Vec<Symbol>,
+    // Indices into `symbols` for each number, and into `numbers` for each symbol
+    number_edges: Vec<Vec<usize>>,
+    symbol_edges: Vec<Vec<usize>>,
+}
+
+impl SchematicGraph {
+    pub fn parse(
+        input: &str,
+        ragged: bool,
+        adjacency: &Adjacency,
+    ) -> Result<SchematicGraph, ParseError> {
+        SchematicGraph::from_grid(&parse_grid(input, ragged)?, adjacency)
+    }
+
+    pub fn from_grid(
+        schematic: &Grid<char>,
+        adjacency: &Adjacency,
+    ) -> Result<SchematicGraph, ParseError> {
+        let mut num_grid: Grid<Option<usize>> =
+            Grid::new(schematic.width(), schematic.height(), None);
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+
+        for (y, row) in schematic.rows().enumerate() {
+            let (row_numbers, row_symbols) = parse_row(row, y)?;
+            for n in row_numbers {
+                for x in n.start..n.end {
+                    num_grid[(x, y)] = Some(numbers.len());
+                }
+                numbers.push(n);
+            }
+            symbols.extend(row_symbols);
+        }
+
+        let mut number_edges = vec![vec![]; numbers.len()];
+        let mut symbol_edges = vec![vec![]; symbols.len()];
+        for (s_idx, s) in symbols.iter().enumerate() {
+            let mut touching: Vec<usize> = schematic
+                .neighbours(s.x, s.y, adjacency)
+                .into_iter()
+                .filter_map(|p| num_grid[p])
+                .collect();
+            touching.sort();
+            touching.dedup();
+            for &n_idx in &touching {
+                number_edges[n_idx].push(s_idx);
+            }
+            symbol_edges[s_idx] = touching;
+        }
+
+        Ok(SchematicGraph {
+            numbers,
+            symbols,
+            number_edges,
+            symbol_edges,
+        })
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.symbol_edges
+            .iter()
+            .enumerate()
+            .flat_map(|(s_idx, ns)| ns.iter().map(move |&n_idx| (n_idx, s_idx)))
+    }
+
+    pub fn symbols_of(&self, number: usize) -> impl Iterator<Item = &Symbol> {
+        self.number_edges[number].iter().map(|&s| &self.symbols[s])
+    }
+
+    pub fn numbers_of(&self, symbol: usize) -> impl Iterator<Item = &Number> {
+        self.symbol_edges[symbol].iter().map(|&n| &self.numbers[n])
+    }
+
+    // Numbers adjacent to at least one symbol of the given type
+    pub fn numbers_touching(&self, symbol: char) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .enumerate()
+            .filter(move |&(n_idx, _)| self.symbols_of(n_idx).any(|s| s.symbol == symbol))
+            .map(|(_, n)| n)
+    }
+
+    // Indices of symbols with exactly `count` adjacent numbers
+    pub fn symbols_with_neighbours(&self, count: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.symbols.len()).filter(move |&s_idx| self.symbol_edges[s_idx].len() == count)
+    }
+
+    // Numbers not adjacent to any symbol
+    pub fn isolated_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .zip(&self.number_edges)
+            .filter(|(_, ss)| ss.is_empty())
+            .map(|(n, _)| n)
+    }
+
+    pub fn is_part_number(&self, number: usize) -> bool {
+        !self.number_edges[number].is_empty()
+    }
+
+    // The symbol's ratio if it counts as a gear under `rule`
+    pub fn gear_ratio(&self, symbol: usize, rule: &GearRule) -> Result<Option<i64>, ParseError> {
+        let s = &self.symbols[symbol];
+        if !rule.matches(s.symbol, self.symbol_edges[symbol].len()) {
+            return Ok(None);
+        }
+        rule.aggregate(self.numbers_of(symbol).map(|n| n.value))
+            .map(Some)
+            .ok_or_else(|| GearRule::overflow(s.x, s.y))
+    }
+
+    // Each number fits in an i32, so even a schematic full of them can't overflow an i64
+    pub fn part_number_sum(&self) -> i64 {
+        (0..self.numbers.len())
+            .filter(|&n_idx| self.is_part_number(n_idx))
+            .map(|n_idx| self.numbers[n_idx].value as i64)
+            .sum()
+    }
+
+    pub fn gear_ratio_sum(&self, rule: &GearRule) -> Result<i64, ParseError> {
+        let mut sum: i64 = 0;
+        for (s_idx, s) in self.symbols.iter().enumerate() {
+            if let Some(ratio) = self.gear_ratio(s_idx, rule)? {
+                sum = sum
+                    .checked_add(ratio)
+                    .ok_or_else(|| GearRule::overflow(s.x, s.y))?;
+            }
+        }
+        Ok(sum)
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph schematic {\n");
+        for (n_idx, n) in self.numbers.iter().enumerate() {
+            writeln!(
+                out,
+                "    n{} [shape=box, label=\"{} ({}, {}..{})\"];",
+                n_idx, n.value, n.row, n.start, n.end
+            )
+            .expect("");
+        }
+        for (s_idx, s) in self.symbols.iter().enumerate() {
+            writeln!(
+                out,
+                "    s{} [shape=ellipse, label=\"{} ({}, {})\"];",
+                s_idx,
+                escape(s.symbol),
+                s.x,
+                s.y
+            )
+            .expect("");
+        }
+        for (n_idx, s_idx) in self.edges() {
+            writeln!(out, "    n{} -- s{};", n_idx, s_idx).expect("");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let numbers: Vec<String> = self
+            .numbers
+            .iter()
+            .map(|n| {
+                format!(
+                    "{{\"value\":{},\"row\":{},\"start\":{},\"end\":{}}}",
+                    n.value, n.row, n.start, n.end
+                )
+            })
+            .collect();
+        let symbols: Vec<String> = self
+            .symbols
+            .iter()
+            .map(|s| {
+                format!(
+                    "{{\"symbol\":\"{}\",\"x\":{},\"y\":{}}}",
+                    escape(s.symbol),
+                    s.x,
+                    s.y
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges()
+            .map(|(n_idx, s_idx)| format!("[{},{}]", n_idx, s_idx))
+            .collect();
+        format!(
+            "{{\"numbers\":[{}],\"symbols\":[{}],\"edges\":[{}]}}\n",
+            numbers.join(","),
+            symbols.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+impl Number {
+    // Whether (x, y) neighbours one of the digits, without wrapping around the edges
+    pub fn touches(&self, x: usize, y: usize, neighbourhood: &Neighbourhood) -> bool {
+        match *neighbourhood {
+            Neighbourhood::VonNeumann => {
+                (y == self.row && (x + 1 == self.start || x == self.end))
+                    || (y.abs_diff(self.row) == 1 && x >= self.start && x < self.end)
+            }
+            Neighbourhood::Chebyshev(k) => {
+                y.abs_diff(self.row) <= k && x + k >= self.start && x < self.end + k
+            }
+        }
+    }
+
+    // `end` is the column just past the last digit
+    fn new(digits: &str, row: usize, end: usize) -> Result<Number, ParseError> {
+        let start = end - digits.len();
+        let value = digits.parse().map_err(|_| ParseError {
+            line: row + 1,
+            column: start + 1,
+            message: format!("number {} is too large", digits),
+        })?;
+        Ok(Number {
+            value,
+            row,
+            start,
+            end,
+        })
+    }
+}
+
+// Reads a schematic, either requiring every line to be the same width or,
+// with `ragged` set, treating missing cells at the end of short lines as '.'
+pub fn parse_grid(input: &str, ragged: bool) -> Result<Grid<char>, ParseError> {
+    if ragged {
+        Ok(Grid::parse_ragged(input, '.'))
+    } else {
+        Grid::parse(input)
+    }
+}
+
+// Splits one row of the schematic into its numbers and symbols
+pub fn parse_row(row: &[char], y: usize) -> Result<(Vec<Number>, Vec<Symbol>), ParseError> {
+    let mut numbers = vec![];
+    let mut symbols = vec![];
+    let mut curr_num = String::new();
+    for (x, &c) in row.iter().enumerate() {
+        match c {
+            '0'..='9' => {
+                curr_num.push(c);
+                continue;
+            }
+            '.' => {}
+            c if c.is_whitespace() || c.is_control() => {
+                return Err(ParseError {
+                    line: y + 1,
+                    column: x + 1,
+                    message: format!("unexpected character {:?}", c),
+                });
+            }
+            symbol => symbols.push(Symbol { symbol, x, y }),
+        }
+        if !curr_num.is_empty() {
+            numbers.push(Number::new(&curr_num, y, x)?);
+            curr_num = String::new();
+        }
+    }
+    if !curr_num.is_empty() {
+        numbers.push(Number::new(&curr_num, y, row.len())?);
+    }
+    Ok((numbers, symbols))
+}
+
+// Escapes a symbol for use inside a double-quoted DOT or JSON string
+fn escape(c: char) -> String {
+    match c {
+        '"' => String::from("\\\""),
+        '\\' => String::from("\\\\"),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng::Rng, stream::stream_solution};
+
+    fn four_way_gear() -> GearRule {
+        GearRule {
+            count: NeighbourCount::Exactly(4),
+            ..GearRule::default()
+        }
+    }
+
+    #[test]
+    fn products_of_several_numbers_use_an_i64() {
+        let input = "999.999\n...*...\n999.999\n";
+        let graph = SchematicGraph::parse(input, false, &Adjacency::default()).expect("valid");
+        assert_eq!(graph.gear_ratio_sum(&four_way_gear()), Ok(996005996001));
+        let streamed = stream_solution(
+            input.as_bytes(),
+            &four_way_gear(),
+            &Neighbourhood::Chebyshev(1),
+            false,
+        );
+        assert_eq!(streamed, Ok((3996, 996005996001)));
+    }
+
+    #[test]
+    fn a_ratio_too_big_for_an_i64_is_an_error() {
+        let input = "999999999.999999999\n.........*.........\n999999999.999999999\n";
+        let graph = SchematicGraph::parse(input, false, &Adjacency::default()).expect("valid");
+        let error = Err(GearRule::overflow(9, 1));
+        assert_eq!(graph.gear_ratio_sum(&four_way_gear()), error);
+        let streamed = stream_solution(
+            input.as_bytes(),
+            &four_way_gear(),
+            &Neighbourhood::Chebyshev(1),
+            false,
+        );
+        assert_eq!(streamed.map(|(_, p2)| p2), error);
+    }
+
+    #[test]
+    fn a_total_too_big_for_an_i64_is_an_error() {
+        // Each gear's ratio is about 4.6e18, which fits, but the third takes the total past 9.2e18
+        let gear = "2147483647*2147483647\n.....................\n";
+        let input = gear.repeat(3);
+        let graph = SchematicGraph::parse(&input, false, &Adjacency::default()).expect("valid");
+        assert_eq!(
+            graph.gear_ratio(0, &GearRule::default()),
+            Ok(Some(4611686014132420609))
+        );
+        assert_eq!(
+            graph.gear_ratio_sum(&GearRule::default()),
+            Err(GearRule::overflow(10, 4))
+        );
+    }
+
+    #[test]
+    fn sum_and_max_combine_the_numbers() {
+        let input = "999.999\n...*...\n999.999\n";
+        let graph = SchematicGraph::parse(input, false, &Adjacency::default()).expect("valid");
+        for (aggregation, expected) in [(Aggregation::Sum, 3996), (Aggregation::Max, 999)] {
+            let rule = GearRule {
+                aggregation,
+                ..four_way_gear()
+            };
+            assert_eq!(graph.gear_ratio_sum(&rule), Ok(expected));
+        }
+    }
+
+    fn solve(input: &str, neighbourhood: Neighbourhood, wrap: bool, rule: &GearRule) -> (i64, i64) {
+        let adjacency = Adjacency {
+            neighbourhood,
+            wrap,
+        };
+        let graph = SchematicGraph::parse(input, false, &adjacency).expect("valid");
+        (
+            graph.part_number_sum(),
+            graph.gear_ratio_sum(rule).expect("fits"),
+        )
+    }
+
+    #[test]
+    fn four_neighbours_leave_out_diagonals() {
+        let input = "1.2\n.*.\n3.4\n";
+        let sum = GearRule {
+            count: NeighbourCount::AtLeast(1),
+            aggregation: Aggregation::Sum,
+            ..GearRule::default()
+        };
+        assert_eq!(
+            solve(input, Neighbourhood::Chebyshev(1), false, &sum),
+            (10, 10)
+        );
+        assert_eq!(solve(input, Neighbourhood::VonNeumann, false, &sum), (0, 0));
+        assert_eq!(
+            solve(
+                "..2\n.1*\n...\n",
+                Neighbourhood::VonNeumann,
+                false,
+                &GearRule::default()
+            ),
+            (3, 2)
+        );
+    }
+
+    #[test]
+    fn a_radius_reaches_further() {
+        let input = "5...\n..*.\n....\n...7\n";
+        let rule = GearRule::default();
+        assert_eq!(
+            solve(input, Neighbourhood::Chebyshev(1), false, &rule),
+            (0, 0)
+        );
+        assert_eq!(
+            solve(input, Neighbourhood::Chebyshev(2), false, &rule),
+            (12, 35)
+        );
+    }
+
+    #[test]
+    fn wrapping_joins_opposite_edges() {
+        let input = "*...\n....\n...6\n";
+        let rule = GearRule::default();
+        assert_eq!(
+            solve(input, Neighbourhood::Chebyshev(1), false, &rule),
+            (0, 0)
+        );
+        assert_eq!(
+            solve(input, Neighbourhood::Chebyshev(1), true, &rule),
+            (6, 0)
+        );
+        assert_eq!(solve(input, Neighbourhood::VonNeumann, true, &rule), (0, 0));
+    }
+
+    #[test]
+    fn a_number_reached_twice_by_wrapping_counts_once() {
+        // On a 3-wide, 1-high grid the '*' sees both digits of 12, from the left and the right,
+        // and every row above and below it is itself
+        let one = GearRule {
+            count: NeighbourCount::Exactly(1),
+            aggregation: Aggregation::Sum,
+            ..GearRule::default()
+        };
+        for neighbourhood in [Neighbourhood::Chebyshev(1), Neighbourhood::Chebyshev(3)] {
+            assert_eq!(solve("12*\n", neighbourhood, true, &one), (12, 12));
+        }
+    }
+
+    // Both answers, linking every number to every symbol any of its digits reaches
+    fn brute_force(grid: &Grid<char>, adjacency: &Adjacency, rule: &GearRule) -> (i64, i64) {
+        let (width, height) = (grid.width(), grid.height());
+        let mut numbers: Vec<(i64, Vec<(usize, usize)>)> = vec![];
+        for y in 0..height {
+            let mut x = 0;
+            while x < width {
+                let start = x;
+                while x < width && grid[(x, y)].is_ascii_digit() {
+                    x += 1;
+                }
+                if x > start {
+                    let digits: String = grid.row(y)[start..x].iter().collect();
+                    let cells = (start..x).map(|xp| (xp, y)).collect();
+                    numbers.push((digits.parse().expect("short numbers"), cells));
+                } else {
+                    x += 1;
+                }
+            }
+        }
+        let symbols: Vec<(char, (usize, usize))> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&p| !grid[p].is_ascii_digit() && grid[p] != '.')
+            .map(|p| (grid[p], p))
+            .collect();
+        let touching = |cells: &[(usize, usize)], s: (usize, usize)| {
+            cells
+                .iter()
+                .any(|&c| adjacency.reaches(c, s, width, height))
+        };
+
+        let part_sum = numbers
+            .iter()
+            .filter(|(_, cells)| symbols.iter().any(|&(_, s)| touching(cells, s)))
+            .map(|(value, _)| value)
+            .sum();
+        let mut gear_sum = 0;
+        for &(symbol, s) in &symbols {
+            let values: Vec<i32> = numbers
+                .iter()
+                .filter(|(_, cells)| touching(cells, s))
+                .map(|&(value, _)| value as i32)
+                .collect();
+            if rule.matches(symbol, values.len()) {
+                gear_sum += rule.aggregate(values.into_iter()).expect("fits");
+            }
+        }
+        (part_sum, gear_sum)
+    }
+
+    #[test]
+    fn every_adjacency_matches_brute_force() {
+        let neighbourhoods = [
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Chebyshev(1),
+            Neighbourhood::Chebyshev(2),
+            Neighbourhood::Chebyshev(3),
+        ];
+        let rules = [
+            GearRule::default(),
+            GearRule {
+                symbols: vec!['*', '#'],
+                count: NeighbourCount::AtLeast(1),
+                aggregation: Aggregation::Sum,
+            },
+        ];
+        let mut rng = Rng::new(9);
+        for _ in 0..500 {
+            let (width, height) = (1 + rng.below(8), 1 + rng.below(6));
+            let text: String = (0..height)
+                .map(|_| {
+                    let row: String = (0..width)
+                        .map(|_| match rng.below(20) {
+                            0..=7 => char::from_digit(rng.below(10) as u32, 10).expect(""),
+                            8..=16 => '.',
+                            _ => ['*', '#'][rng.below(2)],
+                        })
+                        .collect();
+                    row + "\n"
+                })
+                .collect();
+            let grid = parse_grid(&text, false).expect("valid");
+            for neighbourhood in neighbourhoods {
+                for wrap in [false, true] {
+                    let adjacency = Adjacency {
+                        neighbourhood,
+                        wrap,
+                    };
+                    for rule in &rules {
+                        let expected = brute_force(&grid, &adjacency, rule);
+                        let graph = SchematicGraph::from_grid(&grid, &adjacency).expect("valid");
+                        let found = (
+                            graph.part_number_sum(),
+                            graph.gear_ratio_sum(rule).expect("fits"),
+                        );
+                        assert_eq!(found, expected, "{:?} on\n{}", adjacency, text);
+                        if !wrap {
+                            let streamed =
+                                stream_solution(text.as_bytes(), rule, &neighbourhood, false);
+                            assert_eq!(streamed, Ok(expected), "{:?} on\n{}", adjacency, text);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use crate::{
+    grid::{Neighbourhood, ParseError},
+    schematic::{parse_row, GearRule, Number, Symbol},
+};
+
+struct Row {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+}
+
+// Solves both parts while holding only a few rows of the schematic at a time
+// A row's numbers and symbols can only touch rows within the adjacency radius,
+// so with the usual 8-neighbourhood the window is three rows and each row is
+// settled as soon as the row after it has been read
+// Wrap-around joins the last row to the first, so it can't be streamed; only a neighbourhood
+// is taken, never a wrapping adjacency
+pub fn stream_solution<R: BufRead>(
+    mut reader: R,
+    rule: &GearRule,
+    neighbourhood: &Neighbourhood,
+    ragged: bool,
+) -> Result<(i64, i64), ParseError> {
+    let radius = neighbourhood.radius();
+    let mut window: VecDeque<Option<Row>> = (0..2 * radius + 1).map(|_| None).collect();
+    let mut sums = (0, 0);
+    let mut width = None;
+    let mut y = 0;
+    // Blank lines are only rows of the schematic if something follows them
+    let mut blank_run = 0;
+    let mut line = String::new();
+    let mut chars = vec![];
+
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(|e| ParseError {
+            line: y + blank_run + 1,
+            column: 1,
+            message: format!("could not read line: {}", e),
+        })?;
+        if read == 0 {
+            break;
+        }
+        let text = line.trim_end_matches('\n');
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if text.is_empty() {
+            blank_run += 1;
+            continue;
+        }
+
+        let mut pending = vec![""; blank_run];
+        pending.push(text);
+        blank_run = 0;
+        for text in pending {
+            chars.clear();
+            chars.extend(text.chars());
+            if !ragged {
+                let w = *width.get_or_insert(chars.len());
+                if chars.len() != w {
+                    return Err(ParseError {
+                        line: y + 1,
+                        column: chars.len().min(w) + 1,
+                        message: format!(
+                            "line is {} characters wide but the first line is {}",
+                            chars.len(),
+                            w
+                        ),
+                    });
+                }
+            }
+
+            let (numbers, symbols) = parse_row(&chars, y)?;
+            window.pop_front();
+            window.push_back(Some(Row { numbers, symbols }));
+            settle_middle(&window, rule, neighbourhood, &mut sums)?;
+            y += 1;
+        }
+    }
+
+    for _ in 0..radius {
+        window.pop_front();
+        window.push_back(None);
+        settle_middle(&window, rule, neighbourhood, &mut sums)?;
+    }
+
+    Ok(sums)
+}
+
+// Adds the middle row's part numbers and gear ratios, given the rows above and below it
+fn settle_middle(
+    window: &VecDeque<Option<Row>>,
+    rule: &GearRule,
+    neighbourhood: &Neighbourhood,
+    sums: &mut (i64, i64),
+) -> Result<(), ParseError> {
+    let Some(curr) = &window[window.len() / 2] else {
+        return Ok(());
+    };
+    let rows = || window.iter().flatten();
+
+    for n in &curr.numbers {
+        if rows().any(|r| r.symbols.iter().any(|s| n.touches(s.x, s.y, neighbourhood))) {
+            sums.0 += n.value as i64;
+        }
+    }
+
+    for s in &curr.symbols {
+        let touching: Vec<i32> = rows()
+            .flat_map(|r| r.numbers.iter())
+            .filter(|n| n.touches(s.x, s.y, neighbourhood))
+            .map(|n| n.value)
+            .collect();
+        if rule.matches(s.symbol, touching.len()) {
+            sums.1 = rule
+                .aggregate(touching.into_iter())
+                .and_then(|ratio| sums.1.checked_add(ratio))
+                .ok_or_else(|| GearRule::overflow(s.x, s.y))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+";
+
+    #[test]
+    fn matches_the_example() {
+        let result = stream_solution(
+            EXAMPLE.as_bytes(),
+            &GearRule::default(),
+            &Neighbourhood::Chebyshev(1),
+            false,
+        );
+        assert_eq!(result, Ok((4361, 467835)));
+    }
+}
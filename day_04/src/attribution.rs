@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{count::Count, won_cards, Card, CardError, Rules};
+
+// How many copies one original card is responsible for
+// Every copy is won by exactly one earlier card instance, so the copies form a forest
+// rooted at the originals, and the originals plus their descendants add up to the part 2 total
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Attribution<C> {
+    pub id: u32,
+    // Cards the original wins copies of directly, and how many of each
+    pub won: Vec<(u32, u32)>,
+    // Copies descended from the original, directly or through the copies it won
+    pub descendants: C,
+}
+
+impl<C: Count> Attribution<C> {
+    pub fn direct(&self) -> u64 {
+        self.won.iter().map(|&(_, count)| count as u64).sum()
+    }
+}
+
+pub fn attribution<C: Count>(
+    cards: &BTreeMap<u32, Card>,
+    rules: &Rules,
+) -> Result<Vec<Attribution<C>>, CardError> {
+    let mut report: BTreeMap<u32, Attribution<C>> = BTreeMap::new();
+    // A card's descendants only depend on cards played after it, so fill them in backwards
+    let order: Vec<(&u32, &Card)> = rules.play_order(cards).collect();
+    for &(&id, card) in order.iter().rev() {
+        let won = won_cards(cards, id, card.matching_numbers(), rules)?;
+        let mut descendants = C::zero();
+        for &(w, count) in &won {
+            // A copy wrapped back onto an already-played card doesn't win anything itself
+            let per_copy = if rules.plays_after(id, w) {
+                C::one().checked_add(&report[&w].descendants)
+            } else {
+                Some(C::one())
+            };
+            descendants = per_copy
+                .and_then(|per_copy| per_copy.checked_mul(count))
+                .and_then(|extra| descendants.checked_add(&extra))
+                .ok_or(CardError::Overflow { id })?;
+        }
+        report.insert(
+            id,
+            Attribution {
+                id,
+                won,
+                descendants,
+            },
+        );
+    }
+    Ok(report.into_values().collect())
+}
+
+// The `n` cards with the most descendants, most productive first, ties broken by id
+pub fn top_n<C: Count>(report: &[Attribution<C>], n: usize) -> Vec<&Attribution<C>> {
+    let mut sorted: Vec<&Attribution<C>> = report.iter().collect();
+    sorted.sort_by(|a, b| b.descendants.cmp(&a.descendants).then(a.id.cmp(&b.id)));
+    sorted.truncate(n);
+    sorted
+}
+
+// The card -> copies graph, with each card labelled by how many copies descend from it
+// Edges to cards that were already played (wrapped copies) are dashed, since they break the
+// order of play; edges carrying more than one copy per instance are labelled with the count
+pub fn to_dot<C: Count>(report: &[Attribution<C>], rules: &Rules) -> String {
+    let mut out = String::from("digraph cards {\n    rankdir=LR;\n");
+    for a in report {
+        writeln!(
+            out,
+            "    c{} [label=\"Card {}\\n+{}\"];",
+            a.id, a.id, a.descendants
+        )
+        .expect("");
+    }
+    for a in report {
+        for &(w, count) in &a.won {
+            let mut attrs = vec![];
+            if !rules.plays_after(a.id, w) {
+                attrs.push(String::from("style=dashed"));
+            }
+            if count > 1 {
+                attrs.push(format!("label=\"x{}\"", count));
+            }
+            let attrs = if attrs.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attrs.join(", "))
+            };
+            writeln!(out, "    c{} -> c{}{};", a.id, w, attrs).expect("");
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generate::generate_cards,
+        parse_cards,
+        scoring::{CopyRule, Doubling, NextCards, PreviousCards, ScaledNextCards},
+        solution, EndPolicy,
+    };
+
+    const EXAMPLE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
+
+    fn rules(policy: EndPolicy, copies: &dyn CopyRule) -> Rules<'_> {
+        Rules {
+            policy,
+            points: &Doubling,
+            copies,
+        }
+    }
+
+    #[test]
+    fn example_report() {
+        let cards = parse_cards(EXAMPLE).expect("valid cards");
+        let rules = rules(EndPolicy::Error, &NextCards);
+        let report = attribution::<u64>(&cards, &rules).expect("no errors");
+
+        let direct: Vec<u64> = report.iter().map(|a| a.direct()).collect();
+        assert_eq!(direct, vec![4, 2, 2, 1, 0, 0]);
+        let descendants: Vec<u64> = report.iter().map(|a| a.descendants).collect();
+        assert_eq!(descendants, vec![14, 6, 3, 1, 0, 0]);
+        assert_eq!(report[2].won, vec![(4, 1), (5, 1)]);
+
+        let top: Vec<u32> = top_n(&report, 3).iter().map(|a| a.id).collect();
+        assert_eq!(top, vec![1, 2, 3]);
+        // Cards 5 and 6 both have no descendants, so the lower id comes first
+        let bottom: Vec<u32> = top_n(&report, 6)[4..].iter().map(|a| a.id).collect();
+        assert_eq!(bottom, vec![5, 6]);
+    }
+
+    #[test]
+    fn dot_labels_descendants_and_marks_wrapped_copies() {
+        let cards = parse_cards(EXAMPLE).expect("valid cards");
+        let dot = to_dot(
+            &attribution::<u64>(&cards, &rules(EndPolicy::Error, &NextCards)).expect("no errors"),
+            &rules(EndPolicy::Error, &NextCards),
+        );
+        assert!(dot.contains("c1 [label=\"Card 1\\n+14\"];"));
+        assert!(dot.contains("    c1 -> c5;\n"));
+        assert!(!dot.contains("dashed"));
+
+        // Card 1 has 4 matches, so wrapping back from card 1 lands on cards 6, 5, 4 and 3
+        let wrap = rules(EndPolicy::Wrap, &PreviousCards);
+        let dot = to_dot(
+            &attribution::<u64>(&cards, &wrap).expect("no errors"),
+            &wrap,
+        );
+        assert!(dot.contains("    c1 -> c6 [style=dashed];\n"));
+
+        let scaled = rules(EndPolicy::Error, &ScaledNextCards);
+        let dot = to_dot(
+            &attribution::<u64>(&cards, &scaled).expect("no errors"),
+            &scaled,
+        );
+        assert!(dot.contains("    c2 -> c3 [label=\"x2\"];\n"));
+    }
+
+    #[test]
+    fn originals_and_descendants_add_up_to_part_2() {
+        let copy_rules: [&dyn CopyRule; 3] = [&NextCards, &PreviousCards, &ScaledNextCards];
+        for seed in 1..20 {
+            let input = generate_cards(60, seed, true);
+            let cards = parse_cards(&input).expect("valid cards");
+            for copies in copy_rules {
+                for policy in [EndPolicy::Truncate, EndPolicy::Wrap] {
+                    let rules = rules(policy, copies);
+                    let report = attribution::<u128>(&cards, &rules).expect("no errors");
+                    let total: u128 = report.iter().map(|a| 1 + a.descendants).sum();
+                    let (_, part2) = solution::<u128>(&input, &rules).expect("no errors");
+                    assert_eq!(total, part2, "seed {} with {:?}", seed, policy);
+                }
+            }
+        }
+    }
+}
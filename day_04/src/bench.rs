@@ -0,0 +1,49 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crate::number_set::NumberSet;
+
+// Times building both sets of every card and counting their matches, once with NumberSet and
+// once with the HashSet intersection it replaced, and checks the two agree
+// The numbers are split out beforehand so only the set work is timed
+// Reproduce with: day_04 --generate 2000000 --seed 1 > input.txt && day_04 --bench-sets
+pub fn bench_sets(input: &str) -> (Duration, Duration) {
+    let cards: Vec<(Vec<i32>, Vec<i32>)> = input
+        .lines()
+        .filter_map(|line| {
+            let (_, numbers) = line.split_once(':')?;
+            let (winning, ours) = numbers.split_once('|')?;
+            let parse = |s: &str| -> Vec<i32> {
+                s.split_whitespace()
+                    .map(|x| x.parse().expect("Numbers should be integers"))
+                    .collect()
+            };
+            Some((parse(winning), parse(ours)))
+        })
+        .collect();
+
+    let now = Instant::now();
+    let bits: usize = cards
+        .iter()
+        .map(|(w, o)| {
+            NumberSet::from_numbers(w.clone()).matches(&NumberSet::from_numbers(o.clone()))
+        })
+        .sum();
+    let bits_time = now.elapsed();
+
+    let now = Instant::now();
+    let hashed: usize = cards
+        .iter()
+        .map(|(w, o)| {
+            let w: HashSet<i32> = w.iter().copied().collect();
+            let o: HashSet<i32> = o.iter().copied().collect();
+            w.intersection(&o).count()
+        })
+        .sum();
+    let hash_time = now.elapsed();
+
+    assert_eq!(bits, hashed, "NumberSet and HashSet disagree");
+    (bits_time, hash_time)
+}
@@ -0,0 +1,210 @@
+use std::{cmp::Ordering, fmt};
+
+// An unsigned count that reports overflow instead of wrapping
+pub trait Count: Clone + Ord + fmt::Display {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_u128(n: u128) -> Option<Self>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, n: u32) -> Option<Self>;
+}
+
+macro_rules! impl_count {
+    ($t:ty) => {
+        impl Count for $t {
+            fn zero() -> Self {
+                0
+            }
+
+            fn one() -> Self {
+                1
+            }
+
+            fn from_u128(n: u128) -> Option<Self> {
+                n.try_into().ok()
+            }
+
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, n: u32) -> Option<Self> {
+                <$t>::checked_mul(*self, n as $t)
+            }
+        }
+    };
+}
+
+impl_count!(u64);
+impl_count!(u128);
+
+// Arbitrary-precision unsigned integer, with just the operations copy counting needs
+// Stored as little-endian base 10^9 limbs so it prints without any division
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+const LIMB_BASE: u64 = 1_000_000_000;
+
+impl BigUint {
+    fn add(&self, other: &BigUint) -> BigUint {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = carry
+                + *self.limbs.get(i).unwrap_or(&0) as u64
+                + *other.limbs.get(i).unwrap_or(&0) as u64;
+            limbs.push((sum % LIMB_BASE) as u32);
+            carry = sum / LIMB_BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        BigUint { limbs }
+    }
+}
+
+impl Count for BigUint {
+    fn zero() -> Self {
+        BigUint { limbs: vec![] }
+    }
+
+    fn one() -> Self {
+        BigUint { limbs: vec![1] }
+    }
+
+    fn from_u128(mut n: u128) -> Option<Self> {
+        let mut limbs = vec![];
+        while n > 0 {
+            limbs.push((n % LIMB_BASE as u128) as u32);
+            n /= LIMB_BASE as u128;
+        }
+        Some(BigUint { limbs })
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self.add(other))
+    }
+
+    fn checked_mul(&self, n: u32) -> Option<Self> {
+        if n == 0 {
+            return Some(BigUint::zero());
+        }
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 2);
+        let mut carry = 0;
+        for &limb in &self.limbs {
+            let product = limb as u64 * n as u64 + carry;
+            limbs.push((product % LIMB_BASE) as u32);
+            carry = product / LIMB_BASE;
+        }
+        while carry > 0 {
+            limbs.push((carry % LIMB_BASE) as u32);
+            carry /= LIMB_BASE;
+        }
+        Some(BigUint { limbs })
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        // No leading zero limbs are ever stored, so a longer number is a bigger one
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.limbs.split_last() {
+            None => write!(f, "0"),
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{:09}", limb)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::Rng;
+
+    fn big(n: u128) -> BigUint {
+        BigUint::from_u128(n).expect("always fits")
+    }
+
+    // Values of every size up to a u128, so sums and products land on both sides of
+    // limb boundaries
+    fn random_u128(rng: &mut Rng) -> u128 {
+        let value = (rng.next() as u128) << 64 | rng.next() as u128;
+        value >> rng.below(128)
+    }
+
+    #[test]
+    fn matches_u128_where_it_fits() {
+        let mut rng = Rng::new(3);
+        for _ in 0..20000 {
+            let (a, b) = (random_u128(&mut rng), random_u128(&mut rng));
+            let n = match rng.below(4) {
+                0 => 0,
+                1 => rng.below(10) as u32,
+                _ => rng.next() as u32,
+            };
+
+            assert_eq!(big(a).to_string(), a.to_string());
+            assert_eq!(big(a).cmp(&big(b)), a.cmp(&b), "{} against {}", a, b);
+            if let Some(sum) = a.checked_add(b) {
+                assert_eq!(big(a).checked_add(&big(b)), Some(big(sum)), "{} + {}", a, b);
+            }
+            if let Some(product) = a.checked_mul(n as u128) {
+                assert_eq!(big(a).checked_mul(n), Some(big(product)), "{} * {}", a, n);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_and_limb_boundaries_print_in_full() {
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(big(0), BigUint::zero());
+        assert_eq!(big(7).checked_mul(0), Some(BigUint::zero()));
+        assert_eq!(BigUint::zero().checked_add(&BigUint::one()), Some(big(1)));
+        assert_eq!(big(1_000_000_000).to_string(), "1000000000");
+        assert_eq!(
+            big(999_999_999).checked_add(&big(1)),
+            Some(big(1_000_000_000))
+        );
+        assert_eq!(
+            big(1_000_000_000_000_000_001).to_string(),
+            "1000000000000000001"
+        );
+    }
+
+    #[test]
+    fn keeps_going_past_u128() {
+        let mut power = BigUint::one();
+        for _ in 0..200 {
+            power = power.checked_mul(2).expect("never overflows");
+        }
+        assert_eq!(
+            power.to_string(),
+            "1606938044258990275541962092341162602522202993782792835301376"
+        );
+        assert!(power > big(u128::MAX));
+        let doubled = power.checked_add(&power).expect("never overflows");
+        assert_eq!(doubled, power.checked_mul(2).expect("never overflows"));
+        assert!(doubled > power);
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::Write;
+
+// Small xorshift generator so inputs are reproducible from a seed without extra crates
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    // xorshift never leaves zero, so a zero seed is moved off it
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+// Writes `count` random cards in the puzzle's format: 10 winning numbers and 25 of ours, all 1-99
+// Most cards have no matches, a few have one and the odd one is a big winner, which keeps
+// copy counts from growing exponentially over a few hundred cards
+// Unless `past_end` is set, no card wins copies of cards beyond the last one
+pub fn generate_cards(count: u32, seed: u64, past_end: bool) -> String {
+    let mut rng = Rng::new(seed);
+    let id_width = count.to_string().len();
+    let mut out = String::new();
+
+    for id in 1..=count {
+        let mut matches = match rng.below(8) {
+            0 => rng.below(11),
+            1 | 2 => 1,
+            _ => 0,
+        } as usize;
+        if !past_end {
+            matches = matches.min((count - id) as usize);
+        }
+
+        // The first 10 of a shuffled 1-99 are the winning numbers, the next 25 - matches are misses
+        let mut pool: Vec<u32> = (1..=99).collect();
+        for i in 0..35 {
+            let j = i + rng.below((pool.len() - i) as u64) as usize;
+            pool.swap(i, j);
+        }
+        let winning = &pool[..10];
+        let mut ours: Vec<u32> = winning[..matches].to_vec();
+        ours.extend(&pool[10..35 - matches]);
+        for i in 0..ours.len() {
+            let j = i + rng.below((ours.len() - i) as u64) as usize;
+            ours.swap(i, j);
+        }
+
+        write!(out, "Card {:>width$}:", id, width = id_width).expect("");
+        for n in winning {
+            write!(out, " {:>2}", n).expect("");
+        }
+        out.push_str(" |");
+        for n in ours {
+            write!(out, " {:>2}", n).expect("");
+        }
+        out.push('\n');
+    }
+    out
+}
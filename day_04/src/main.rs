@@ -1,40 +1,384 @@
-use std::{collections::HashSet, fs};
+mod attribution;
+mod bench;
+mod count;
+mod generate;
+mod number_set;
+mod scoring;
 
-fn solution(input_str: &str) -> (i32, i32) {
-    let mut sump1 = 0;
-    let mut copy_vec = vec![1; input_str.lines().count()];
+use std::{collections::BTreeMap, env, fmt, fs};
+
+use attribution::{attribution, to_dot, top_n};
+use bench::bench_sets;
+use count::{BigUint, Count};
+use generate::generate_cards;
+use number_set::NumberSet;
+use scoring::{
+    CopyRule, Direction, Doubling, Fibonacci, Linear, NextCards, PointsRule, PreviousCards,
+    ScaledNextCards,
+};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum CardError {
+    // A line that isn't "Card N: winning | ours", with its 1-based line number
+    Parse { line: usize, message: String },
+    Duplicate { id: u32 },
+    // `id` wins a copy of `missing`, which isn't in the list
+    Missing { id: u32, missing: u32 },
+    // `id` wins a copy of `won`, which is beyond the first or last card
+    // Copies run away from the card that wins them, so `won` below `id` means before the first
+    PastEnd { id: u32, won: i64 },
+    // A count no longer fits in the integer type, while handling card `id`
+    Overflow { id: u32 },
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            CardError::Duplicate { id } => write!(f, "card {} appears more than once", id),
+            CardError::Missing { id, missing } => write!(
+                f,
+                "card {} wins a copy of card {}, which is not in the list",
+                id, missing
+            ),
+            CardError::PastEnd { id, won } => write!(
+                f,
+                "card {} wins a copy of card {}, which is {} of the table",
+                id,
+                won,
+                if *won < *id as i64 {
+                    "before the start"
+                } else {
+                    "past the end"
+                }
+            ),
+            CardError::Overflow { id } => {
+                write!(f, "count overflowed while handling card {}", id)
+            }
+        }
+    }
+}
+
+// What happens when a card wins copies of cards beyond the last one
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum EndPolicy {
+    // Copies past the end are dropped
+    Truncate,
+    // Copies past the end wrap around to the other end of the table
+    // Each card still only hands out its copies once, when its turn comes in the single
+    // pass through the table, so copies wrapped onto already-played cards are counted but
+    // don't win anything themselves
+    Wrap,
+    Error,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct Card {
+    id: u32,
+    winning: NumberSet,
+    ours: NumberSet,
+}
+
+impl Card {
+    fn from_string(input: &str, line: usize) -> Result<Card, CardError> {
+        let error = |message: &str| CardError::Parse {
+            line,
+            message: message.to_string(),
+        };
+
+        let (label, numbers) = input.split_once(':').ok_or_else(|| error("missing ':'"))?;
+        let id = label
+            .trim()
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .ok_or_else(|| error("expected \"Card <id>\" before ':'"))?;
+        let (winning_numbers, our_numbers) = numbers
+            .split_once('|')
+            .ok_or_else(|| error("missing '|'"))?;
+
+        let parse_set = |numbers: &str| -> Result<NumberSet, CardError> {
+            numbers
+                .split_whitespace()
+                .map(|x| {
+                    x.parse()
+                        .map_err(|_| error(&format!("{} is not a number", x)))
+                })
+                .collect::<Result<Vec<i32>, CardError>>()
+                .map(NumberSet::from_numbers)
+        };
+
+        Ok(Card {
+            id,
+            winning: parse_set(winning_numbers)?,
+            ours: parse_set(our_numbers)?,
+        })
+    }
+
+    fn matching_numbers(&self) -> usize {
+        self.winning.matches(&self.ours)
+    }
+}
+
+// Cards keyed by id, so the input can be in any order and have gaps
+fn parse_cards(input_str: &str) -> Result<BTreeMap<u32, Card>, CardError> {
+    let mut cards = BTreeMap::new();
     for (idx, line) in input_str.lines().enumerate() {
-        let (winning_numbers, our_numbers) =
-            line.split_once(':').expect("").1.split_once('|').expect("");
-        let winning_set: HashSet<i32> =
-            HashSet::from_iter(winning_numbers.trim().split(' ').flat_map(|x| x.parse()));
-        let our_set: HashSet<i32> =
-            HashSet::from_iter(our_numbers.trim().split(' ').flat_map(|x| x.parse()));
+        if line.trim().is_empty() {
+            continue;
+        }
+        let card = Card::from_string(line, idx + 1)?;
+        if cards.contains_key(&card.id) {
+            return Err(CardError::Duplicate { id: card.id });
+        }
+        cards.insert(card.id, card);
+    }
+    Ok(cards)
+}
 
-        let matching_numbers = winning_set.intersection(&our_set).count();
+// Everything that decides how a table of cards is scored
+#[derive(Clone, Copy)]
+struct Rules<'a> {
+    policy: EndPolicy,
+    points: &'a dyn PointsRule,
+    copies: &'a dyn CopyRule,
+}
+
+impl Rules<'_> {
+    // Cards in the order they are played, so each has all its copies before its turn
+    fn play_order<'c>(
+        &self,
+        cards: &'c BTreeMap<u32, Card>,
+    ) -> Box<dyn Iterator<Item = (&'c u32, &'c Card)> + 'c> {
+        match self.copies.direction() {
+            Direction::Forward => Box::new(cards.iter()),
+            Direction::Backward => Box::new(cards.iter().rev()),
+        }
+    }
 
-        if matching_numbers > 0 {
-            sump1 += 2_i32.pow(matching_numbers as u32 - 1);
-            let copies = copy_vec[idx];
-            for i in 1..=matching_numbers {
-                copy_vec[idx + i] += copies;
+    // Whether `other` is played after `id`, so copies won of it still get to play
+    fn plays_after(&self, id: u32, other: u32) -> bool {
+        match self.copies.direction() {
+            Direction::Forward => other > id,
+            Direction::Backward => other < id,
+        }
+    }
+}
+
+// The ids that one copy of card `id` wins copies of, and how many of each,
+// after applying the end-of-table policy
+fn won_cards(
+    cards: &BTreeMap<u32, Card>,
+    id: u32,
+    matching_numbers: usize,
+    rules: &Rules,
+) -> Result<Vec<(u32, u32)>, CardError> {
+    let (Some(&first), Some(&last)) = (cards.keys().next(), cards.keys().next_back()) else {
+        return Ok(vec![]);
+    };
+    let (first, last) = (first as i64, last as i64);
+
+    let mut won_ids = vec![];
+    for (distance, count) in rules.copies.wins(matching_numbers) {
+        let won = match rules.copies.direction() {
+            Direction::Forward => id as i64 + distance as i64,
+            Direction::Backward => id as i64 - distance as i64,
+        };
+        let won = if (first..=last).contains(&won) {
+            won
+        } else {
+            match rules.policy {
+                EndPolicy::Truncate => continue,
+                EndPolicy::Wrap => first + (won - first).rem_euclid(last - first + 1),
+                EndPolicy::Error => return Err(CardError::PastEnd { id, won }),
             }
+        } as u32;
+        if !cards.contains_key(&won) {
+            return Err(CardError::Missing { id, missing: won });
         }
+        won_ids.push((won, count));
     }
+    Ok(won_ids)
+}
 
-    let sump2: i32 = copy_vec.iter().sum();
-    (sump1, sump2)
+// Generic over the count type so callers can pick how wide the counts are
+fn solution<C: Count>(input_str: &str, rules: &Rules) -> Result<(C, C), CardError> {
+    let cards = parse_cards(input_str)?;
+
+    let mut sump1 = C::zero();
+    let mut copies: BTreeMap<u32, C> = cards.keys().map(|&id| (id, C::one())).collect();
+    for (&id, card) in rules.play_order(&cards) {
+        let matching_numbers = card.matching_numbers();
+
+        sump1 = rules
+            .points
+            .points(matching_numbers)
+            .and_then(C::from_u128)
+            .and_then(|points| sump1.checked_add(&points))
+            .ok_or(CardError::Overflow { id })?;
+        let card_copies = copies[&id].clone();
+        for (won, count) in won_cards(&cards, id, matching_numbers, rules)? {
+            let total = copies
+                .get_mut(&won)
+                .expect("won_cards only returns known ids");
+            *total = card_copies
+                .checked_mul(count)
+                .and_then(|extra| total.checked_add(&extra))
+                .ok_or(CardError::Overflow { id })?;
+        }
+    }
+
+    let mut sump2 = C::zero();
+    for (&id, count) in &copies {
+        sump2 = sump2.checked_add(count).ok_or(CardError::Overflow { id })?;
+    }
+    Ok((sump1, sump2))
+}
+
+fn show<C: Count>(result: Result<(C, C), CardError>) -> Result<(String, String), CardError> {
+    result.map(|(p1, p2)| (p1.to_string(), p2.to_string()))
+}
+
+// Tries u64, then u128, then a big integer, stopping at the first that doesn't overflow
+fn solution_auto(input_str: &str, rules: &Rules) -> Result<(String, String), CardError> {
+    match show(solution::<u64>(input_str, rules)) {
+        Err(CardError::Overflow { .. }) => {}
+        result => return result,
+    }
+    match show(solution::<u128>(input_str, rules)) {
+        Err(CardError::Overflow { .. }) => {}
+        result => return result,
+    }
+    show(solution::<BigUint>(input_str, rules))
 }
 
 fn main() {
+    // Usage: day_04 [--past-end truncate|wrap|error] [--width u64|u128|big|auto]
+    //               [--points doubling|linear|fibonacci] [--copies next|previous|scaled]
+    //        day_04 --generate count [--seed n] [--allow-past-end]
+    //        day_04 [--past-end ...] --attribution [--top n] [--dot path]
+    //        day_04 --bench-sets
     let file_path = "input.txt";
 
+    let mut policy = EndPolicy::Error;
+    let mut points: &dyn PointsRule = &Doubling;
+    let mut copies: &dyn CopyRule = &NextCards;
+    let mut width = String::from("auto");
+    let mut generate = None;
+    let mut seed = 1;
+    let mut allow_past_end = false;
+    let mut report = false;
+    let mut top = None;
+    let mut dot_path = None;
+    let mut bench = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--past-end" => {
+                policy = match args.next().as_deref() {
+                    Some("truncate") => EndPolicy::Truncate,
+                    Some("wrap") => EndPolicy::Wrap,
+                    Some("error") => EndPolicy::Error,
+                    _ => panic!("--past-end expects truncate, wrap or error"),
+                };
+            }
+            "--points" => {
+                points = match args.next().as_deref() {
+                    Some("doubling") => &Doubling,
+                    Some("linear") => &Linear,
+                    Some("fibonacci") => &Fibonacci,
+                    _ => panic!("--points expects doubling, linear or fibonacci"),
+                };
+            }
+            "--copies" => {
+                copies = match args.next().as_deref() {
+                    Some("next") => &NextCards,
+                    Some("previous") => &PreviousCards,
+                    Some("scaled") => &ScaledNextCards,
+                    _ => panic!("--copies expects next, previous or scaled"),
+                };
+            }
+            "--width" => width = args.next().expect("--width expects u64, u128, big or auto"),
+            "--generate" => {
+                generate = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .expect("--generate expects a number of cards"),
+                );
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--seed expects a number");
+            }
+            "--allow-past-end" => allow_past_end = true,
+            "--attribution" => report = true,
+            "--top" => {
+                top = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .expect("--top expects a number of cards"),
+                );
+            }
+            "--bench-sets" => bench = true,
+            "--dot" => dot_path = Some(args.next().expect("--dot expects an output path")),
+            x => panic!("Unexpected argument {}", x),
+        }
+    }
+
+    // Writes a generated input to stdout instead of solving
+    if let Some(count) = generate {
+        print!("{}", generate_cards(count, seed, allow_past_end));
+        return;
+    }
+
     let input_str = fs::read_to_string(file_path).expect("Should have been able to read the file");
 
+    // Times the card sets against the HashSet version instead of solving
+    if bench {
+        let (bits, hashed) = bench_sets(&input_str);
+        println!("NumberSet: {:.2?}", bits);
+        println!("HashSet: {:.2?}", hashed);
+        return;
+    }
+    let rules = Rules {
+        policy,
+        points,
+        copies,
+    };
+
+    // Reports which original cards the copies came from
+    if report {
+        let cards = parse_cards(&input_str).unwrap_or_else(|e| panic!("Invalid cards: {}", e));
+        let report = attribution::<BigUint>(&cards, &rules)
+            .unwrap_or_else(|e| panic!("Invalid cards: {}", e));
+        let shown = match top {
+            Some(n) => top_n(&report, n),
+            None => report.iter().collect(),
+        };
+        println!("card\tdirect\tdescendants");
+        for a in shown {
+            println!("{}\t{}\t{}", a.id, a.direct(), a.descendants);
+        }
+        if let Some(path) = dot_path {
+            fs::write(path, to_dot(&report, &rules))
+                .expect("Should have been able to write the file");
+        }
+        return;
+    }
+
     use std::time::Instant;
     let now = Instant::now();
     for _ in 0..1 {
-        let (p1, p2) = solution(&input_str);
+        let result = match width.as_str() {
+            "u64" => show(solution::<u64>(&input_str, &rules)),
+            "u128" => show(solution::<u128>(&input_str, &rules)),
+            "big" => show(solution::<BigUint>(&input_str, &rules)),
+            "auto" => solution_auto(&input_str, &rules),
+            x => panic!("Unknown width {}", x),
+        };
+        let (p1, p2) = result.unwrap_or_else(|e| panic!("Invalid cards: {}", e));
         println!("{p1}");
         println!("{p2}");
     }
@@ -42,3 +386,197 @@ fn main() {
     let elapsed = now.elapsed();
     println!("Elapsed: {:.2?}", elapsed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Card 2 wins copies of cards 3, 4 and 5, but the table stops at 3
+    // Wrapped around, those become cards 1 and 2, so card 2 wins a copy of itself
+    const PAST_END: &str = "Card 1: 1 2 | 1 9
+Card 2: 1 2 3 | 1 2 3
+Card 3: 5 | 7
+";
+
+    const EXAMPLE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
+
+    fn rules(policy: EndPolicy) -> Rules<'static> {
+        Rules {
+            policy,
+            points: &Doubling,
+            copies: &NextCards,
+        }
+    }
+
+    #[test]
+    fn truncate_drops_copies_past_the_end() {
+        let result = solution::<u64>(PAST_END, &rules(EndPolicy::Truncate));
+        assert_eq!(result, Ok((5, 6)));
+    }
+
+    #[test]
+    fn wrap_sends_copies_back_to_the_start() {
+        let cards = parse_cards(PAST_END).expect("valid cards");
+        let won = won_cards(&cards, 2, 3, &rules(EndPolicy::Wrap));
+        assert_eq!(won, Ok(vec![(3, 1), (1, 1), (2, 1)]));
+
+        // Card 1 gets 2 copies from card 2, card 2 gets 2 from card 1 and 2 from itself
+        let result = solution::<u64>(PAST_END, &rules(EndPolicy::Wrap));
+        assert_eq!(result, Ok((5, 10)));
+    }
+
+    #[test]
+    fn error_names_the_card_and_the_missing_copy() {
+        let result = solution::<u64>(PAST_END, &rules(EndPolicy::Error));
+        assert_eq!(result, Err(CardError::PastEnd { id: 2, won: 4 }));
+    }
+
+    #[test]
+    fn backward_errors_say_before_the_start() {
+        let error = CardError::PastEnd { id: 4, won: 6 };
+        assert_eq!(
+            error.to_string(),
+            "card 4 wins a copy of card 6, which is past the end of the table"
+        );
+
+        let rules = Rules {
+            copies: &PreviousCards,
+            ..rules(EndPolicy::Error)
+        };
+        let error = solution::<u64>(EXAMPLE, &rules).expect_err("card 2 reaches card 0");
+        assert_eq!(error, CardError::PastEnd { id: 2, won: 0 });
+        assert_eq!(
+            error.to_string(),
+            "card 2 wins a copy of card 0, which is before the start of the table"
+        );
+    }
+
+    #[test]
+    fn scoring_rules_on_the_example() {
+        let with = |points: &'static dyn PointsRule, copies: &'static dyn CopyRule| Rules {
+            policy: EndPolicy::Truncate,
+            points,
+            copies,
+        };
+        assert_eq!(
+            solution::<u64>(EXAMPLE, &with(&Doubling, &NextCards)),
+            Ok((13, 30))
+        );
+        assert_eq!(
+            solution::<u64>(EXAMPLE, &with(&Linear, &NextCards)),
+            Ok((9, 30))
+        );
+        assert_eq!(
+            solution::<u64>(EXAMPLE, &with(&Fibonacci, &NextCards)),
+            Ok((10, 30))
+        );
+        // Played from card 6 down; card 4 gives card 3 a copy, whose 2 instances give cards 2
+        // and 1 one each, and card 2's 3 instances give card 1 three more
+        assert_eq!(
+            solution::<u64>(EXAMPLE, &with(&Doubling, &PreviousCards)),
+            Ok((13, 14))
+        );
+        // Card 1 wins 4 copies each of cards 2-5, then every instance of card 2 wins 2 each of
+        // cards 3 and 4, and so on
+        assert_eq!(
+            solution::<u64>(EXAMPLE, &with(&Doubling, &ScaledNextCards)),
+            Ok((13, 147))
+        );
+    }
+
+    #[test]
+    fn generated_cards_stay_inside_the_table() {
+        for seed in 1..50 {
+            let input = generate_cards(200, seed, false);
+            let result = solution::<BigUint>(&input, &rules(EndPolicy::Error));
+            assert!(
+                !matches!(result, Err(CardError::PastEnd { .. })),
+                "seed {} produced {:?}",
+                seed,
+                result.err()
+            );
+        }
+    }
+
+    #[test]
+    fn generated_cards_can_run_past_the_end_when_allowed() {
+        let hits = (1..50)
+            .filter(|&seed| {
+                let input = generate_cards(200, seed, true);
+                let result = solution::<BigUint>(&input, &rules(EndPolicy::Error));
+                matches!(result, Err(CardError::PastEnd { .. }))
+            })
+            .count();
+        assert!(hits > 0);
+    }
+
+    // `count` cards where each one matches every card after it, so card n has 2^(n-1)
+    // copies and part 2 is 2^count - 1
+    fn doubling_table(count: u32) -> String {
+        (1..=count)
+            .map(|id| {
+                let numbers: Vec<String> = (1..=count - id).map(|n| n.to_string()).collect();
+                let numbers = numbers.join(" ");
+                format!("Card {}: {} | {}\n", id, numbers, numbers)
+            })
+            .collect()
+    }
+
+    fn linear_rules() -> Rules<'static> {
+        Rules {
+            policy: EndPolicy::Error,
+            points: &Linear,
+            copies: &NextCards,
+        }
+    }
+
+    #[test]
+    fn auto_width_stops_at_the_first_that_fits() {
+        let rules = linear_rules();
+
+        let input = doubling_table(40);
+        assert_eq!(solution::<u64>(&input, &rules), Ok((780, (1 << 40) - 1)));
+        assert_eq!(
+            solution_auto(&input, &rules),
+            Ok((String::from("780"), String::from("1099511627775")))
+        );
+
+        // Past a u64, but u128 still holds it
+        let input = doubling_table(100);
+        assert!(matches!(
+            solution::<u64>(&input, &rules),
+            Err(CardError::Overflow { .. })
+        ));
+        assert_eq!(solution::<u128>(&input, &rules), Ok((4950, (1 << 100) - 1)));
+        assert_eq!(
+            solution_auto(&input, &rules),
+            Ok((
+                String::from("4950"),
+                String::from("1267650600228229401496703205375")
+            ))
+        );
+    }
+
+    #[test]
+    fn auto_width_falls_back_to_big_integers_past_u128() {
+        let rules = linear_rules();
+        let input = doubling_table(130);
+        assert!(matches!(
+            solution::<u128>(&input, &rules),
+            Err(CardError::Overflow { .. })
+        ));
+        assert_eq!(
+            solution_auto(&input, &rules),
+            Ok((
+                String::from("8385"),
+                String::from("1361129467683753853853498429727072845823")
+            ))
+        );
+    }
+}
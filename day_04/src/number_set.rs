@@ -0,0 +1,127 @@
+// A set of card numbers built for counting matches quickly
+// Puzzle numbers are all small, so they normally fit a 128-bit bitset and a match
+// count is two ANDs and popcounts; anything outside 0-127 falls back to a sorted vector
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum NumberSet {
+    Bits([u64; 2]),
+    Sorted(Vec<i32>),
+}
+
+impl NumberSet {
+    pub fn from_numbers(mut numbers: Vec<i32>) -> NumberSet {
+        if numbers.iter().all(|&n| (0..128).contains(&n)) {
+            let mut bits = [0; 2];
+            for n in numbers {
+                bits[n as usize / 64] |= 1 << (n % 64);
+            }
+            return NumberSet::Bits(bits);
+        }
+        numbers.sort_unstable();
+        numbers.dedup();
+        NumberSet::Sorted(numbers)
+    }
+
+    // How many numbers are in both sets
+    pub fn matches(&self, other: &NumberSet) -> usize {
+        match (self, other) {
+            (NumberSet::Bits(a), NumberSet::Bits(b)) => {
+                ((a[0] & b[0]).count_ones() + (a[1] & b[1]).count_ones()) as usize
+            }
+            (a, b) => merge_count(&a.to_sorted(), &b.to_sorted()),
+        }
+    }
+
+    fn to_sorted(&self) -> Vec<i32> {
+        match self {
+            NumberSet::Bits(bits) => (0..128)
+                .filter(|&n| bits[n as usize / 64] & (1 << (n % 64)) != 0)
+                .collect(),
+            NumberSet::Sorted(numbers) => numbers.clone(),
+        }
+    }
+}
+
+// Counts the values common to two sorted, deduplicated slices
+fn merge_count(a: &[i32], b: &[i32]) -> usize {
+    let (mut i, mut j, mut count) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                count += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::generate::Rng;
+
+    // Numbers drawn from `range`, with repeats, so sets of every size and density turn up
+    fn numbers(rng: &mut Rng, range: std::ops::Range<i32>) -> Vec<i32> {
+        let count = rng.below(30);
+        let width = (range.end - range.start) as u64;
+        (0..count)
+            .map(|_| range.start + rng.below(width) as i32)
+            .collect()
+    }
+
+    fn hash_matches(a: &[i32], b: &[i32]) -> usize {
+        let a: HashSet<i32> = a.iter().copied().collect();
+        let b: HashSet<i32> = b.iter().copied().collect();
+        a.intersection(&b).count()
+    }
+
+    #[test]
+    fn matches_agrees_with_hash_set_intersection() {
+        let mut rng = Rng::new(7);
+        // Small ranges give bitsets, the others force the sorted fallback on one or both sides
+        let ranges = [0..128, 0..20, -40..40, 100..200, -300..-1, 120..136];
+        for _ in 0..20000 {
+            let ra = ranges[rng.below(ranges.len() as u64) as usize].clone();
+            let a = numbers(&mut rng, ra);
+            let rb = ranges[rng.below(ranges.len() as u64) as usize].clone();
+            let b = numbers(&mut rng, rb);
+
+            let (sa, sb) = (
+                NumberSet::from_numbers(a.clone()),
+                NumberSet::from_numbers(b.clone()),
+            );
+            let expected = hash_matches(&a, &b);
+            assert_eq!(sa.matches(&sb), expected, "{:?} and {:?}", a, b);
+            assert_eq!(sb.matches(&sa), expected, "{:?} and {:?}", b, a);
+        }
+    }
+
+    #[test]
+    fn picks_bits_only_for_0_to_127() {
+        assert!(matches!(
+            NumberSet::from_numbers(vec![0, 64, 127]),
+            NumberSet::Bits(_)
+        ));
+        assert!(matches!(
+            NumberSet::from_numbers(vec![0, 128]),
+            NumberSet::Sorted(_)
+        ));
+        assert!(matches!(
+            NumberSet::from_numbers(vec![-1, 5]),
+            NumberSet::Sorted(_)
+        ));
+    }
+
+    #[test]
+    fn mixed_representations_compare_by_value() {
+        let bits = NumberSet::from_numbers(vec![1, 5, 64, 127]);
+        let sorted = NumberSet::from_numbers(vec![-3, 5, 127, 128, 1000]);
+        assert_eq!(bits.matches(&sorted), 2);
+        assert_eq!(sorted.matches(&bits), 2);
+    }
+}
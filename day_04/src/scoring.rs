@@ -0,0 +1,134 @@
+// How many points a card with `matches` matching numbers is worth (part 1)
+// None means the points don't fit in a u128
+pub trait PointsRule {
+    fn points(&self, matches: usize) -> Option<u128>;
+}
+
+// The puzzle's rule: 1 point for the first match, doubled for each one after
+pub struct Doubling;
+
+// 1 point per match
+pub struct Linear;
+
+// 1, 2, 3, 5, 8, ... points for 1, 2, 3, 4, 5, ... matches
+pub struct Fibonacci;
+
+impl PointsRule for Doubling {
+    fn points(&self, matches: usize) -> Option<u128> {
+        match matches {
+            0 => Some(0),
+            m => 1_u128.checked_shl(m as u32 - 1),
+        }
+    }
+}
+
+impl PointsRule for Linear {
+    fn points(&self, matches: usize) -> Option<u128> {
+        Some(matches as u128)
+    }
+}
+
+impl PointsRule for Fibonacci {
+    fn points(&self, matches: usize) -> Option<u128> {
+        if matches == 0 {
+            return Some(0);
+        }
+        // The current and previous terms, so nothing past the answer has to fit
+        let (mut a, mut b) = (1_u128, 1_u128);
+        for _ in 1..matches {
+            (a, b) = (a.checked_add(b)?, a);
+        }
+        Some(a)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+// Which cards a card wins copies of (part 2)
+// Copies all flow one way, and cards are played in that order so every card has
+// received all its copies before it is played
+pub trait CopyRule {
+    fn direction(&self) -> Direction;
+    // For a card with `matches` matching numbers: how many cards away each won card is,
+    // and how many copies of it each instance of the card wins
+    fn wins(&self, matches: usize) -> Vec<(u32, u32)>;
+}
+
+// The puzzle's rule: one copy each of the next `matches` cards
+pub struct NextCards;
+
+// One copy each of the previous `matches` cards
+pub struct PreviousCards;
+
+// `matches` copies each of the next `matches` cards
+pub struct ScaledNextCards;
+
+impl CopyRule for NextCards {
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn wins(&self, matches: usize) -> Vec<(u32, u32)> {
+        (1..=matches as u32).map(|d| (d, 1)).collect()
+    }
+}
+
+impl CopyRule for PreviousCards {
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn wins(&self, matches: usize) -> Vec<(u32, u32)> {
+        (1..=matches as u32).map(|d| (d, 1)).collect()
+    }
+}
+
+impl CopyRule for ScaledNextCards {
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn wins(&self, matches: usize) -> Vec<(u32, u32)> {
+        (1..=matches as u32).map(|d| (d, matches as u32)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(rule: &dyn PointsRule) -> Vec<u128> {
+        (0..7).map(|m| rule.points(m).expect("fits")).collect()
+    }
+
+    #[test]
+    fn points_rules() {
+        assert_eq!(points(&Doubling), vec![0, 1, 2, 4, 8, 16, 32]);
+        assert_eq!(points(&Linear), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(points(&Fibonacci), vec![0, 1, 2, 3, 5, 8, 13]);
+
+        assert_eq!(Doubling.points(128), Some(1 << 127));
+        assert_eq!(Doubling.points(129), None);
+        // The 186th Fibonacci number is the last that fits in a u128
+        assert_eq!(
+            Fibonacci.points(185),
+            Some(332825110087067562321196029789634457848)
+        );
+        assert_eq!(Fibonacci.points(186), None);
+    }
+
+    #[test]
+    fn copy_rules() {
+        assert_eq!(NextCards.direction(), Direction::Forward);
+        assert_eq!(NextCards.wins(3), vec![(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(PreviousCards.direction(), Direction::Backward);
+        assert_eq!(PreviousCards.wins(3), vec![(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(ScaledNextCards.direction(), Direction::Forward);
+        assert_eq!(ScaledNextCards.wins(3), vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(ScaledNextCards.wins(0), vec![]);
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Write},
+};
+
+use crate::{
+    interval_map::{IntervalMap, Segment},
+    StoDMap,
+};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum AlmanacError {
+    // A line that doesn't fit the almanac format, with its 1-based line number
+    Parse { line: usize, message: String },
+    // Two "from-to-to map:" sections for the same pair of categories
+    Duplicate { from: String, to: String },
+    // Following the maps forwards leads back to where they started
+    Cycle { categories: Vec<String> },
+    UnknownCategory { category: String },
+    NoPath { from: String, to: String },
+    // A map used backwards sends two values to the same place, so it has no inverse
+    NotInvertible { from: String, to: String },
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlmanacError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            AlmanacError::Duplicate { from, to } => {
+                write!(f, "there is more than one {}-to-{} map", from, to)
+            }
+            AlmanacError::Cycle { categories } => {
+                write!(f, "the maps form a cycle: {}", categories.join(" -> "))
+            }
+            AlmanacError::UnknownCategory { category } => {
+                write!(f, "no map mentions the category {}", category)
+            }
+            AlmanacError::NoPath { from, to } => {
+                write!(f, "no chain of maps converts {} to {}", from, to)
+            }
+            AlmanacError::NotInvertible { from, to } => write!(
+                f,
+                "the {}-to-{} map can't be run backwards, it sends several values to one place",
+                from, to
+            ),
+        }
+    }
+}
+
+// One "from-to-to map:" section
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Layer {
+    pub from: String,
+    pub to: String,
+    pub maps: Vec<StoDMap>,
+}
+
+impl Layer {
+    // The layer as a total map: its entries where they apply, identity everywhere else
+    // If entries overlap, the first one listed wins
+    pub fn to_map(&self) -> IntervalMap {
+        let mut segments: Vec<Segment> = self.maps.iter().map(|m| m.to_segment()).collect();
+        segments.push(Segment::new(i64::MIN, i64::MAX, 0));
+        IntervalMap::from_layered(&segments)
+    }
+
+    // The same conversion with the fewest entries: sorted by source, with no overlaps,
+    // touching entries that shift by the same amount joined, and identity entries dropped
+    pub fn canonical(&self) -> Layer {
+        let mut map = self.to_map();
+        map.merge_adjacent();
+        Layer {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            maps: entries_of(&map),
+        }
+    }
+}
+
+// The non-identity parts of a map as almanac entries, in source order
+fn entries_of(map: &IntervalMap) -> Vec<StoDMap> {
+    map.segments()
+        .iter()
+        .filter(|s| s.offset != 0)
+        .map(|s| StoDMap {
+            source: s.start,
+            dest: s.start + s.offset,
+            length: s.end - s.start,
+        })
+        .collect()
+}
+
+// One step of a conversion: a layer, followed forwards or backwards
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Step {
+    pub layer: usize,
+    pub forward: bool,
+}
+
+// The seeds, and the maps between categories as a graph
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Almanac {
+    pub seeds: Vec<i64>,
+    pub layers: Vec<Layer>,
+}
+
+impl Almanac {
+    pub fn parse(input: &str) -> Result<Almanac, AlmanacError> {
+        let error = |line: usize, message: &str| AlmanacError::Parse {
+            line: line + 1,
+            message: message.to_string(),
+        };
+
+        let mut lines = input.lines().enumerate();
+        let seeds = match lines.next() {
+            Some((idx, line)) => line
+                .strip_prefix("seeds:")
+                .ok_or_else(|| error(idx, "expected \"seeds:\" on the first line"))?
+                .split_whitespace()
+                .map(|s| {
+                    s.parse()
+                        .map_err(|_| error(idx, &format!("{} is not a number", s)))
+                })
+                .collect::<Result<Vec<i64>, AlmanacError>>()?,
+            None => return Err(error(0, "the almanac is empty")),
+        };
+
+        let mut layers: Vec<Layer> = vec![];
+        for (idx, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_suffix("map:") {
+                let (from, to) = header
+                    .trim()
+                    .split_once("-to-")
+                    .ok_or_else(|| error(idx, "expected a \"from-to-to map:\" header"))?;
+                if from.is_empty() || to.is_empty() {
+                    return Err(error(idx, "a map header needs two category names"));
+                }
+                if layers.iter().any(|l| l.from == from && l.to == to) {
+                    return Err(AlmanacError::Duplicate {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    });
+                }
+                layers.push(Layer {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    maps: vec![],
+                });
+                continue;
+            }
+
+            let nums = line
+                .split_whitespace()
+                .map(|s| {
+                    s.parse()
+                        .map_err(|_| error(idx, &format!("{} is not a number", s)))
+                })
+                .collect::<Result<Vec<i64>, AlmanacError>>()?;
+            let [dest, source, length] = nums[..] else {
+                return Err(error(idx, "expected \"destination source length\""));
+            };
+            layers
+                .last_mut()
+                .ok_or_else(|| error(idx, "map entry before any map header"))?
+                .maps
+                .push(StoDMap {
+                    source,
+                    dest,
+                    length,
+                });
+        }
+
+        let almanac = Almanac { seeds, layers };
+        almanac.check_acyclic()?;
+        Ok(almanac)
+    }
+
+    // Every category, in the order they first appear
+    pub fn categories(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = vec![];
+        for l in &self.layers {
+            for c in [l.from.as_str(), l.to.as_str()] {
+                if !out.contains(&c) {
+                    out.push(c);
+                }
+            }
+        }
+        out
+    }
+
+    // The shortest chain of layers from one category to another
+    // Layers are followed from source to destination; with `allow_backward`, a layer can also be
+    // followed from its destination back to its source
+    pub fn path(
+        &self,
+        from: &str,
+        to: &str,
+        allow_backward: bool,
+    ) -> Result<Vec<Step>, AlmanacError> {
+        for c in [from, to] {
+            if !self.categories().contains(&c) {
+                return Err(AlmanacError::UnknownCategory {
+                    category: c.to_string(),
+                });
+            }
+        }
+
+        // Breadth first, trying forward steps before backward ones at each category
+        let mut came_from: HashMap<&str, Option<(&str, Step)>> = HashMap::new();
+        came_from.insert(from, None);
+        let mut queue = VecDeque::from([from]);
+        while let Some(curr) = queue.pop_front() {
+            if curr == to {
+                break;
+            }
+            let forward = self
+                .layers
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| l.from == curr);
+            let backward = self
+                .layers
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| allow_backward && l.to == curr);
+            let steps = forward
+                .map(|(layer, l)| {
+                    (
+                        l.to.as_str(),
+                        Step {
+                            layer,
+                            forward: true,
+                        },
+                    )
+                })
+                .chain(backward.map(|(layer, l)| {
+                    (
+                        l.from.as_str(),
+                        Step {
+                            layer,
+                            forward: false,
+                        },
+                    )
+                }));
+            for (next, step) in steps {
+                if !came_from.contains_key(next) {
+                    came_from.insert(next, Some((curr, step)));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut steps = vec![];
+        let mut curr = to;
+        loop {
+            match came_from.get(curr) {
+                None => {
+                    return Err(AlmanacError::NoPath {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    })
+                }
+                Some(None) => break,
+                Some(Some((prev, step))) => {
+                    steps.push(*step);
+                    curr = prev;
+                }
+            }
+        }
+        steps.reverse();
+        Ok(steps)
+    }
+
+    // The route a conversion takes from one category to another
+    // Layers are only run backwards when there is no way through following them forwards,
+    // since a shorter route with a backward step may need an inverse that doesn't exist
+    pub fn route(&self, from: &str, to: &str) -> Result<Vec<Step>, AlmanacError> {
+        self.path(from, to, false)
+            .or_else(|_| self.path(from, to, true))
+    }
+
+    // The single map that converts values of one category to another, along `route`
+    pub fn conversion(&self, from: &str, to: &str) -> Result<IntervalMap, AlmanacError> {
+        let steps = self.route(from, to)?;
+        let mut composed = IntervalMap::identity(i64::MIN..i64::MAX);
+        for step in steps {
+            let layer = &self.layers[step.layer];
+            let map = if step.forward {
+                layer.to_map()
+            } else {
+                layer
+                    .to_map()
+                    .inverse()
+                    .ok_or_else(|| AlmanacError::NotInvertible {
+                        from: layer.from.clone(),
+                        to: layer.to.clone(),
+                    })?
+            };
+            composed = composed.compose(&map);
+        }
+        Ok(composed)
+    }
+
+    // The layers from one category to another, all followed forwards
+    pub fn forward_layers(&self, from: &str, to: &str) -> Result<Vec<&Layer>, AlmanacError> {
+        Ok(self
+            .path(from, to, false)?
+            .iter()
+            .map(|step| &self.layers[step.layer])
+            .collect())
+    }
+
+    // Writes the almanac back out in the puzzle's format
+    // Every entry is written as it was parsed, so parsing the output gives the same almanac
+    pub fn to_text(&self) -> String {
+        let seeds: Vec<String> = self.seeds.iter().map(|s| s.to_string()).collect();
+        let mut out = format!("seeds: {}\n", seeds.join(" "));
+        for layer in &self.layers {
+            write!(out, "\n{}-to-{} map:\n", layer.from, layer.to).expect("");
+            for m in &layer.maps {
+                writeln!(out, "{} {} {}", m.dest, m.source, m.length).expect("");
+            }
+        }
+        out
+    }
+
+    // Every layer replaced by its canonical form
+    pub fn canonical(&self) -> Almanac {
+        Almanac {
+            seeds: self.seeds.clone(),
+            layers: self.layers.iter().map(|l| l.canonical()).collect(),
+        }
+    }
+
+    // An almanac with a single layer doing the whole conversion from one category to another
+    pub fn composed(&self, from: &str, to: &str) -> Result<Almanac, AlmanacError> {
+        Ok(Almanac {
+            seeds: self.seeds.clone(),
+            layers: vec![Layer {
+                from: from.to_string(),
+                to: to.to_string(),
+                maps: entries_of(&self.conversion(from, to)?),
+            }],
+        })
+    }
+
+    // Names the categories along a path, starting with `from`
+    pub fn describe(&self, from: &str, steps: &[Step]) -> String {
+        let mut names = vec![from];
+        for step in steps {
+            let layer = &self.layers[step.layer];
+            names.push(if step.forward { &layer.to } else { &layer.from });
+        }
+        names.join(" -> ")
+    }
+
+    fn check_acyclic(&self) -> Result<(), AlmanacError> {
+        // Depth first from every category, with the current route on a stack
+        fn visit<'a>(
+            almanac: &'a Almanac,
+            curr: &'a str,
+            route: &mut Vec<&'a str>,
+            done: &mut Vec<&'a str>,
+        ) -> Result<(), AlmanacError> {
+            if let Some(pos) = route.iter().position(|&c| c == curr) {
+                let mut categories: Vec<String> =
+                    route[pos..].iter().map(|c| c.to_string()).collect();
+                categories.push(curr.to_string());
+                return Err(AlmanacError::Cycle { categories });
+            }
+            if done.contains(&curr) {
+                return Ok(());
+            }
+            route.push(curr);
+            for l in almanac.layers.iter().filter(|l| l.from == curr) {
+                visit(almanac, &l.to, route, done)?;
+            }
+            route.pop();
+            done.push(curr);
+            Ok(())
+        }
+
+        let mut done = vec![];
+        for c in self.categories() {
+            visit(self, c, &mut vec![], &mut done)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate_almanac, Rng};
+
+    // a -> x -> y -> b forwards, or a <- c -> b in two steps with c-to-a run backwards,
+    // but c-to-a sends both 0..10 and 10..20 onto 0..10, so it has no inverse
+    const ROUTES: &str = "seeds: 1
+
+a-to-x map:
+50 0 50
+0 50 50
+
+x-to-y map:
+1000 50 10
+
+y-to-b map:
+0 1000 10
+
+c-to-a map:
+0 0 10
+0 10 10
+
+c-to-b map:
+5 0 5
+";
+
+    // Entries close together, so they overlap, touch, repeat and leave values alone
+    // (destination equal to source) often; some are empty
+    fn random_almanac(rng: &mut Rng) -> Almanac {
+        let mut next = |n: i64| rng.below(n as u64) as i64;
+        let categories = ["seed", "soil", "water", "location"];
+        let layers = categories
+            .windows(2)
+            .map(|names| Layer {
+                from: names[0].to_string(),
+                to: names[1].to_string(),
+                maps: (0..next(6))
+                    .map(|_| {
+                        let source = next(40) - 10;
+                        let dest = if next(4) == 0 { source } else { next(40) - 10 };
+                        StoDMap {
+                            source,
+                            dest,
+                            length: next(10),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Almanac {
+            seeds: (0..next(4))
+                .flat_map(|_| [next(50) - 10, next(10)])
+                .collect(),
+            layers,
+        }
+    }
+
+    fn merged(mut map: IntervalMap) -> IntervalMap {
+        map.merge_adjacent();
+        map
+    }
+
+    #[test]
+    fn to_text_parses_back_to_the_same_almanac() {
+        let mut rng = Rng::new(11);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng);
+            assert_eq!(Almanac::parse(&almanac.to_text()), Ok(almanac.clone()));
+        }
+    }
+
+    #[test]
+    fn canonical_layers_convert_the_same_way() {
+        let mut rng = Rng::new(12);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng);
+            for (layer, canonical) in almanac.layers.iter().zip(&almanac.canonical().layers) {
+                let (before, after) = (layer.to_map(), canonical.to_map());
+                for x in -30..60 {
+                    assert_eq!(after.apply(x), before.apply(x), "{:?} at {}", layer, x);
+                }
+                assert_eq!(merged(after), merged(before), "{:?}", layer);
+                assert!(canonical
+                    .maps
+                    .iter()
+                    .all(|m| m.length > 0 && m.dest != m.source));
+                assert!(canonical
+                    .maps
+                    .windows(2)
+                    .all(|w| w[0].source + w[0].length <= w[1].source));
+            }
+        }
+    }
+
+    // solution() reads entries the way the puzzle input lays them out, never overlapping,
+    // so the random almanacs are compared in canonical form
+    #[test]
+    fn composed_almanac_gives_the_same_answers() {
+        let mut rng = Rng::new(13);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng).canonical();
+            let composed = almanac.composed("seed", "location").expect("a route");
+            assert_eq!(composed.layers.len(), 1);
+            assert_eq!(
+                crate::solution(&composed, 1),
+                crate::solution(&almanac, 1),
+                "{}",
+                almanac.to_text()
+            );
+        }
+        for seed in 1..20 {
+            let almanac =
+                Almanac::parse(&generate_almanac(5, 30, seed)).expect("generated almanacs parse");
+            let composed = almanac.composed("seed", "location").expect("a route");
+            assert_eq!(crate::solution(&composed, 1), crate::solution(&almanac, 1));
+        }
+    }
+
+    #[test]
+    fn route_prefers_forward_steps_over_a_shorter_backward_one() {
+        let almanac = Almanac::parse(ROUTES).expect("valid almanac");
+        let steps = almanac.route("a", "b").expect("a forward route");
+        assert!(steps.iter().all(|step| step.forward));
+        assert_eq!(almanac.describe("a", &steps), "a -> x -> y -> b");
+
+        let shortest = almanac.path("a", "b", true).expect("a route");
+        assert_eq!(almanac.describe("a", &shortest), "a -> c -> b");
+
+        let steps = almanac.route("x", "a").expect("a backward route");
+        assert_eq!(almanac.describe("x", &steps), "x -> a");
+    }
+
+    #[test]
+    fn conversion_prefers_a_forward_route() {
+        let almanac = Almanac::parse(ROUTES).expect("valid almanac");
+        let conversion = almanac.conversion("a", "b").expect("a forward route");
+        assert_eq!(conversion.apply(3), Some(3));
+        assert_eq!(conversion.apply(60), Some(10));
+        assert_eq!(conversion.apply(150), Some(150));
+    }
+
+    #[test]
+    fn conversion_falls_back_to_backward_steps() {
+        let almanac = Almanac::parse(ROUTES).expect("valid almanac");
+        let conversion = almanac.conversion("x", "a").expect("a backward route");
+        assert_eq!(conversion.apply(53), Some(3));
+        assert_eq!(conversion.apply(3), Some(53));
+        assert_eq!(
+            almanac.conversion("a", "c"),
+            Err(AlmanacError::NotInvertible {
+                from: "c".to_string(),
+                to: "a".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    almanac::{Almanac, AlmanacError},
+    LayerIndex, StoDMap,
+};
+
+// The maps `m` overlaps, found the way squash_many did before LayerIndex: check every map,
+// then sort by source
+pub fn scan_overlapping<'a>(maps: &'a [StoDMap], m: &StoDMap) -> Vec<&'a StoDMap> {
+    let mut out: Vec<&StoDMap> = maps.iter().filter(|o| m.overlaps(o)).collect();
+    out.sort_by_key(|o| o.source);
+    out
+}
+
+// Times looking up the overlaps of every part 2 seed range at every layer, once through a
+// LayerIndex (including building it) and once with the linear scan, and checks they agree
+// The ranges reaching each layer are worked out beforehand so only the lookups are timed
+// Reproduce with: day_05 --generate 100 100000 --seed 3 > input.txt && day_05 --bench-index
+pub fn bench_index(almanac: &Almanac) -> Result<(Duration, Duration), AlmanacError> {
+    let layers = almanac.forward_layers("seed", "location")?;
+    let mut mappings: Vec<StoDMap> = almanac
+        .seeds
+        .chunks(2)
+        .map(|x| StoDMap {
+            source: x[0],
+            dest: x[0],
+            length: x[1],
+        })
+        .collect();
+    let mut reaching = vec![];
+    for layer in &layers {
+        let index = LayerIndex::new(&layer.maps);
+        let next = mappings
+            .iter()
+            .flat_map(|m| m.squash_many(&index))
+            .collect();
+        reaching.push(mappings);
+        mappings = next;
+    }
+
+    let now = Instant::now();
+    let mut indexed = vec![];
+    for (layer, mappings) in layers.iter().zip(&reaching) {
+        let index = LayerIndex::new(&layer.maps);
+        indexed.extend(mappings.iter().map(|m| index.overlapping(m).len()));
+    }
+    let index_time = now.elapsed();
+
+    let now = Instant::now();
+    let mut scanned = vec![];
+    for (layer, mappings) in layers.iter().zip(&reaching) {
+        scanned.extend(
+            mappings
+                .iter()
+                .map(|m| scan_overlapping(&layer.maps, m).len()),
+        );
+    }
+    let scan_time = now.elapsed();
+
+    assert_eq!(indexed, scanned, "LayerIndex and the linear scan disagree");
+    Ok((index_time, scan_time))
+}
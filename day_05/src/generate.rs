@@ -0,0 +1,81 @@
+use std::fmt::Write;
+
+// xorshift64: --seed picks out the same almanac on every run and every machine
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    // xorshift never leaves zero, so a zero seed is moved off it
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+// Values in a generated almanac stay below this, the same scale as real puzzle inputs
+const LIMIT: u64 = 4_000_000_000;
+
+const CATEGORIES: [&str; 8] = [
+    "seed",
+    "soil",
+    "fertilizer",
+    "water",
+    "light",
+    "temperature",
+    "humidity",
+    "location",
+];
+
+// Writes a random almanac in the puzzle's format, with `seed_ranges` part 2 seed ranges and
+// about `entries` entries in each of the seven layers
+// Each layer cuts 0..LIMIT into pieces and lays them out again in a shuffled order, so like
+// the real inputs the entries never overlap; a fifth of the pieces are left out as identity
+// The seed ranges between them cover roughly a quarter of the values
+pub fn generate_almanac(seed_ranges: usize, entries: usize, seed: u64) -> String {
+    let mut rng = Rng::new(seed);
+
+    let average = (LIMIT / 4 / seed_ranges.max(1) as u64).max(1);
+    let mut out = String::from("seeds:");
+    for _ in 0..seed_ranges {
+        let length = 1 + rng.below(2 * average);
+        write!(out, " {} {}", rng.below(LIMIT - length), length).expect("");
+    }
+    out.push('\n');
+
+    // A fifth of the pieces are dropped, so cut a few more to end up near `entries`
+    let pieces = entries * 5 / 4;
+    for names in CATEGORIES.windows(2) {
+        write!(out, "\n{}-to-{} map:\n", names[0], names[1]).expect("");
+
+        let mut cuts: Vec<u64> = (0..pieces.saturating_sub(1))
+            .map(|_| 1 + rng.below(LIMIT - 1))
+            .collect();
+        cuts.extend([0, LIMIT]);
+        cuts.sort();
+        cuts.dedup();
+        let mut order: Vec<(u64, u64)> = cuts.windows(2).map(|w| (w[0], w[1] - w[0])).collect();
+
+        // Shuffle the pieces, then hand out destinations in the shuffled order
+        for i in 0..order.len() {
+            let j = i + rng.below((order.len() - i) as u64) as usize;
+            order.swap(i, j);
+        }
+        let mut dest = 0;
+        for (source, length) in order {
+            if rng.below(5) != 0 {
+                writeln!(out, "{} {} {}", dest, source, length).expect("");
+            }
+            dest += length;
+        }
+    }
+    out
+}
@@ -0,0 +1,481 @@
+use std::{
+    cmp::{max, min, Reverse},
+    collections::BinaryHeap,
+    ops::Range,
+};
+
+// Maps every value in start..end to value + offset
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Segment {
+    pub start: i64,
+    pub end: i64,
+    pub offset: i64,
+}
+
+impl Segment {
+    pub fn new(start: i64, end: i64, offset: i64) -> Segment {
+        Segment { start, end, offset }
+    }
+
+    pub fn image(&self) -> Range<i64> {
+        self.start + self.offset..self.end + self.offset
+    }
+
+    fn clip(&self, range: &Range<i64>) -> Option<Segment> {
+        let start = max(self.start, range.start);
+        let end = min(self.end, range.end);
+        if start < end {
+            Some(Segment::new(start, end, self.offset))
+        } else {
+            None
+        }
+    }
+}
+
+// A piecewise-linear partial map on i64, made of shifted intervals
+// Segments are kept sorted and non-overlapping; values outside every segment are unmapped
+// Every segment's image must fit in an i64
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct IntervalMap {
+    segments: Vec<Segment>,
+}
+
+impl IntervalMap {
+    // Builds a map from segments in any order, or None if any two overlap
+    // Empty segments are dropped
+    pub fn from_segments(mut segments: Vec<Segment>) -> Option<IntervalMap> {
+        segments.retain(|s| s.start < s.end);
+        segments.sort_by_key(|s| s.start);
+        if segments.windows(2).any(|w| w[0].end > w[1].start) {
+            return None;
+        }
+        Some(IntervalMap { segments })
+    }
+
+    // Builds a map from segments that may overlap, where earlier segments take priority
+    // over the parts of later ones they cover
+    pub fn from_layered(segments: &[Segment]) -> IntervalMap {
+        // Sweeps the boundaries left to right, keeping every segment that has started in a
+        // heap so the earliest listed one is on top; finished ones are dropped as they surface
+        let mut by_start: Vec<usize> = (0..segments.len())
+            .filter(|&i| segments[i].start < segments[i].end)
+            .collect();
+        by_start.sort_by_key(|&i| segments[i].start);
+        let mut bounds: Vec<i64> = by_start
+            .iter()
+            .flat_map(|&i| [segments[i].start, segments[i].end])
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        let mut started = BinaryHeap::new();
+        let mut next = 0;
+        let mut pieces: Vec<(usize, Segment)> = vec![];
+        for w in bounds.windows(2) {
+            let (lo, hi) = (w[0], w[1]);
+            while next < by_start.len() && segments[by_start[next]].start <= lo {
+                started.push(Reverse(by_start[next]));
+                next += 1;
+            }
+            while started
+                .peek()
+                .is_some_and(|&Reverse(i)| segments[i].end <= lo)
+            {
+                started.pop();
+            }
+            // Every boundary is in `bounds`, so a segment covering lo covers all of lo..hi
+            if let Some(&Reverse(i)) = started.peek() {
+                match pieces.last_mut() {
+                    Some((last, s)) if *last == i && s.end == lo => s.end = hi,
+                    _ => pieces.push((i, Segment::new(lo, hi, segments[i].offset))),
+                }
+            }
+        }
+        IntervalMap {
+            segments: pieces.into_iter().map(|(_, s)| s).collect(),
+        }
+    }
+
+    pub fn identity(range: Range<i64>) -> IntervalMap {
+        IntervalMap::from_segments(vec![Segment::new(range.start, range.end, 0)])
+            .expect("A single segment can't overlap")
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    // Index of the segment containing x, found by binary search
+    fn segment_index(&self, x: i64) -> Option<usize> {
+        let idx = self.segments.partition_point(|s| s.end <= x);
+        match self.segments.get(idx) {
+            Some(s) if s.start <= x => Some(idx),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, x: i64) -> Option<i64> {
+        self.segment_index(x)
+            .map(|idx| x + self.segments[idx].offset)
+    }
+
+    // The pieces of the map covering `range`, clipped to it, in order
+    pub fn pieces(&self, range: &Range<i64>) -> impl Iterator<Item = Segment> + '_ {
+        let first = self.segments.partition_point(|s| s.end <= range.start);
+        let range = range.clone();
+        self.segments[first..]
+            .iter()
+            .take_while(move |s| s.start < range.end)
+            .filter_map(move |s| s.clip(&range))
+    }
+
+    // The images of the mapped parts of `range`, in the order of the values they came from
+    #[allow(dead_code)]
+    pub fn apply_range(&self, range: &Range<i64>) -> Vec<Range<i64>> {
+        self.pieces(range).map(|s| s.image()).collect()
+    }
+
+    // The smallest value the mapped parts of `range` are sent to
+    pub fn min_image(&self, range: &Range<i64>) -> Option<i64> {
+        self.pieces(range).map(|s| s.start + s.offset).min()
+    }
+
+    // The same map, but only defined on `range`
+    #[allow(dead_code)]
+    pub fn restrict(&self, range: &Range<i64>) -> IntervalMap {
+        IntervalMap {
+            segments: self.pieces(range).collect(),
+        }
+    }
+
+    // The map x -> then(self(x)), defined wherever both steps are
+    pub fn compose(&self, then: &IntervalMap) -> IntervalMap {
+        let mut segments = vec![];
+        for s in &self.segments {
+            for t in then.pieces(&s.image()) {
+                segments.push(Segment::new(
+                    t.start - s.offset,
+                    t.end - s.offset,
+                    s.offset + t.offset,
+                ));
+            }
+        }
+        // Each segment's pieces come out in order and segments don't overlap,
+        // so the result is already sorted
+        let mut map = IntervalMap { segments };
+        map.merge_adjacent();
+        map
+    }
+
+    // Every x that maps into `range`, as sorted, merged ranges
+    // Works whether or not the map is injective
+    pub fn preimage(&self, range: &Range<i64>) -> Vec<Range<i64>> {
+        let found = self
+            .segments
+            .iter()
+            .filter_map(|s| {
+                let start = max(s.start, range.start - s.offset);
+                let end = min(s.end, range.end - s.offset);
+                if start < end {
+                    Some(start..end)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        merge_ranges(found)
+    }
+
+    // The smallest value whose preimage meets one of `targets`, working backwards
+    // from the lowest images rather than trying values one at a time
+    // `targets` must be sorted and merged
+    pub fn smallest_hit(&self, targets: &[Range<i64>]) -> Option<i64> {
+        let mut by_image: Vec<&Segment> = self.segments.iter().collect();
+        by_image.sort_by_key(|s| s.start + s.offset);
+
+        let mut best: Option<i64> = None;
+        for s in by_image {
+            // Later segments can only start higher, so nothing left can beat `best`
+            if best.is_some_and(|b| s.start + s.offset >= b) {
+                break;
+            }
+            // Targets are sorted, so the first one overlapping the domain holds its lowest hit
+            let first = targets.partition_point(|t| t.end <= s.start);
+            if let Some(t) = targets.get(first).filter(|t| t.start < s.end) {
+                let hit = max(s.start, t.start) + s.offset;
+                best = Some(best.map_or(hit, |b| min(b, hit)));
+            }
+        }
+        best
+    }
+
+    // The inverse map, or None if two values map to the same place
+    pub fn inverse(&self) -> Option<IntervalMap> {
+        let mut inverse = IntervalMap::from_segments(
+            self.segments
+                .iter()
+                .map(|s| Segment::new(s.start + s.offset, s.end + s.offset, -s.offset))
+                .collect(),
+        )?;
+        inverse.merge_adjacent();
+        Some(inverse)
+    }
+
+    // Joins touching segments with the same offset, so equal maps have equal segments
+    pub fn merge_adjacent(&mut self) {
+        let mut merged: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for s in self.segments.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end == s.start && last.offset == s.offset => last.end = s.end,
+                _ => merged.push(s),
+            }
+        }
+        self.segments = merged;
+    }
+}
+
+// Sorts ranges and joins any that overlap or touch
+pub fn merge_ranges(mut ranges: Vec<Range<i64>>) -> Vec<Range<i64>> {
+    ranges.retain(|r| r.start < r.end);
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<i64>> = Vec::with_capacity(ranges.len());
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= r.start => last.end = max(last.end, r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::Rng;
+
+    // Every value any test map can touch, with room to spare on both sides
+    const SPAN: Range<i64> = -80..80;
+
+    fn between(rng: &mut Rng, lo: i64, hi: i64) -> i64 {
+        lo + rng.below((hi - lo) as u64) as i64
+    }
+
+    // A handful of short segments that may overlap, touch, be empty or share offsets
+    fn random_segments(rng: &mut Rng) -> Vec<Segment> {
+        (0..rng.below(7))
+            .map(|_| {
+                let start = between(rng, -20, 20);
+                let end = start + between(rng, 0, 8);
+                Segment::new(start, end, between(rng, -3, 3) * 4)
+            })
+            .collect()
+    }
+
+    // The first listed segment containing x decides where it goes
+    fn naive(segments: &[Segment], x: i64) -> Option<i64> {
+        segments
+            .iter()
+            .find(|s| s.start <= x && x < s.end)
+            .map(|s| x + s.offset)
+    }
+
+    fn points(ranges: &[Range<i64>]) -> Vec<i64> {
+        ranges.iter().flat_map(|r| r.clone()).collect()
+    }
+
+    fn is_merged(ranges: &[Range<i64>]) -> bool {
+        ranges.iter().all(|r| r.start < r.end) && ranges.windows(2).all(|w| w[0].end < w[1].start)
+    }
+
+    fn is_well_formed(map: &IntervalMap) -> bool {
+        let s = map.segments();
+        s.iter().all(|s| s.start < s.end) && s.windows(2).all(|w| w[0].end <= w[1].start)
+    }
+
+    #[test]
+    fn from_layered_and_apply_match_the_first_segment() {
+        let mut rng = Rng::new(1);
+        for _ in 0..2000 {
+            let segments = random_segments(&mut rng);
+            let map = IntervalMap::from_layered(&segments);
+            assert!(is_well_formed(&map), "{:?}", map);
+            for x in SPAN {
+                assert_eq!(map.apply(x), naive(&segments, x), "{:?} at {}", segments, x);
+            }
+        }
+    }
+
+    #[test]
+    fn from_segments_only_accepts_disjoint_segments() {
+        let mut rng = Rng::new(2);
+        for _ in 0..2000 {
+            let segments = random_segments(&mut rng);
+            let overlapping = SPAN.into_iter().any(|x| {
+                segments
+                    .iter()
+                    .filter(|s| s.start <= x && x < s.end)
+                    .count()
+                    > 1
+            });
+            match IntervalMap::from_segments(segments.clone()) {
+                Some(map) => {
+                    assert!(!overlapping, "{:?}", segments);
+                    for x in SPAN {
+                        assert_eq!(map.apply(x), naive(&segments, x));
+                    }
+                }
+                None => assert!(overlapping, "{:?}", segments),
+            }
+        }
+    }
+
+    #[test]
+    fn min_image_is_the_smallest_value_reached() {
+        let mut rng = Rng::new(3);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let start = between(&mut rng, -30, 30);
+            let range = start..start + between(&mut rng, 0, 20);
+            let expected = range.clone().filter_map(|x| map.apply(x)).min();
+            assert_eq!(map.min_image(&range), expected, "{:?} on {:?}", map, range);
+        }
+    }
+
+    #[test]
+    fn apply_range_sends_each_mapped_piece_in_order() {
+        let mut rng = Rng::new(9);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let start = between(&mut rng, -30, 30);
+            let range = start..start + between(&mut rng, 0, 20);
+            let images = map.apply_range(&range);
+            assert!(images.iter().all(|r| r.start < r.end), "{:?}", images);
+            let expected: Vec<i64> = range.clone().filter_map(|x| map.apply(x)).collect();
+            assert_eq!(points(&images), expected, "{:?} on {:?}", map, range);
+        }
+    }
+
+    #[test]
+    fn restrict_only_keeps_the_map_inside_the_range() {
+        let mut rng = Rng::new(10);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let start = between(&mut rng, -30, 30);
+            let range = start..start + between(&mut rng, 0, 20);
+            let restricted = map.restrict(&range);
+            assert!(is_well_formed(&restricted), "{:?}", restricted);
+            for x in SPAN {
+                let expected = map.apply(x).filter(|_| range.contains(&x));
+                assert_eq!(restricted.apply(x), expected, "{:?} on {:?}", map, range);
+            }
+        }
+    }
+
+    #[test]
+    fn compose_applies_one_map_then_the_other() {
+        let mut rng = Rng::new(4);
+        for _ in 0..2000 {
+            let first = IntervalMap::from_layered(&random_segments(&mut rng));
+            let then = IntervalMap::from_layered(&random_segments(&mut rng));
+            let composed = first.compose(&then);
+            assert!(is_well_formed(&composed), "{:?}", composed);
+            for x in SPAN {
+                let expected = first.apply(x).and_then(|y| then.apply(y));
+                assert_eq!(composed.apply(x), expected, "{:?} then {:?}", first, then);
+            }
+        }
+    }
+
+    #[test]
+    fn preimage_is_every_value_that_lands_in_the_range() {
+        let mut rng = Rng::new(5);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let start = between(&mut rng, -30, 30);
+            let range = start..start + between(&mut rng, 0, 20);
+            let found = map.preimage(&range);
+            assert!(is_merged(&found), "{:?}", found);
+            let expected: Vec<i64> = SPAN
+                .filter(|&x| map.apply(x).is_some_and(|y| range.contains(&y)))
+                .collect();
+            assert_eq!(points(&found), expected, "{:?} into {:?}", map, range);
+        }
+    }
+
+    #[test]
+    fn smallest_hit_is_the_smallest_value_reached_from_the_targets() {
+        let mut rng = Rng::new(6);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let targets = merge_ranges(
+                (0..rng.below(4))
+                    .map(|_| {
+                        let start = between(&mut rng, -30, 30);
+                        start..start + between(&mut rng, 0, 10)
+                    })
+                    .collect(),
+            );
+            let expected = points(&targets)
+                .into_iter()
+                .filter_map(|x| map.apply(x))
+                .min();
+            assert_eq!(
+                map.smallest_hit(&targets),
+                expected,
+                "{:?} from {:?}",
+                map,
+                targets
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_map_when_nothing_collides() {
+        let mut rng = Rng::new(7);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let mut images: Vec<i64> = SPAN.filter_map(|x| map.apply(x)).collect();
+            let count = images.len();
+            images.sort();
+            images.dedup();
+            let injective = images.len() == count;
+
+            match map.inverse() {
+                Some(inverse) => {
+                    assert!(injective, "{:?} isn't one to one", map);
+                    assert!(is_well_formed(&inverse), "{:?}", inverse);
+                    for x in SPAN {
+                        if let Some(y) = map.apply(x) {
+                            assert_eq!(inverse.apply(y), Some(x), "{:?} at {}", map, x);
+                        }
+                    }
+                    let reached: Vec<i64> = SPAN.filter(|&y| inverse.apply(y).is_some()).collect();
+                    assert_eq!(reached, images, "{:?}", map);
+                }
+                None => assert!(!injective, "{:?} is one to one", map),
+            }
+        }
+    }
+
+    #[test]
+    fn merge_adjacent_keeps_the_map_and_leaves_nothing_to_join() {
+        let mut rng = Rng::new(8);
+        for _ in 0..2000 {
+            let map = IntervalMap::from_layered(&random_segments(&mut rng));
+            let mut merged = map.clone();
+            merged.merge_adjacent();
+            assert!(is_well_formed(&merged), "{:?}", merged);
+            assert!(
+                merged
+                    .segments()
+                    .windows(2)
+                    .all(|w| w[0].end < w[1].start || w[0].offset != w[1].offset),
+                "{:?}",
+                merged
+            );
+            for x in SPAN {
+                assert_eq!(merged.apply(x), map.apply(x));
+            }
+        }
+    }
+}
@@ -1,4 +1,20 @@
-use std::{cmp::min, collections::HashMap, fs, ops::Range};
+mod almanac;
+mod bench;
+mod generate;
+mod interval_map;
+mod svg;
+mod threshold;
+mod validate;
+
+use std::{cmp::min, env, fs, ops::Range, thread};
+
+use almanac::{Almanac, AlmanacError, Layer};
+use bench::bench_index;
+use generate::generate_almanac;
+use interval_map::{merge_ranges, Segment};
+use svg::render_svg;
+use threshold::{count_landing, seeds_at_most, smallest_locations};
+use validate::validate;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 struct StoDMap {
@@ -36,20 +52,13 @@ impl StoDMap {
 
     // Squashes the passed maps into the existing map
     // Returns a vector of maps covering the same source values with updated destinations
-    fn squash_many(&self, others: &Vec<StoDMap>) -> Vec<StoDMap> {
-        let mut overlaps = vec![];
-        for m in others {
-            if self.overlaps(m) {
-                overlaps.push(m);
-            }
-        }
+    fn squash_many(&self, others: &LayerIndex) -> Vec<StoDMap> {
+        let overlaps = others.overlapping(self);
 
         if overlaps.is_empty() {
             return vec![self.clone()];
         }
 
-        overlaps.sort_by_key(|m| m.source);
-
         let mut out_maps = vec![];
 
         let overlap_num = overlaps[0].source;
@@ -115,107 +124,435 @@ impl StoDMap {
         }
         false
     }
+}
 
-    fn from_string(input: &str) -> StoDMap {
-        let in_vec: Vec<&str> = input.split(' ').filter(|s| !s.is_empty()).collect();
-        let dest = in_vec[0]
-            .parse::<i64>()
-            .expect("from_string() expects a string containing 3 numbers separated by whitespace");
-        let source = in_vec[1]
-            .parse::<i64>()
-            .expect("from_string() expects a string containing 3 numbers separated by whitespace");
-        let length = in_vec[2]
-            .parse::<i64>()
-            .expect("from_string() expects a string containing 3 numbers separated by whitespace");
+// A layer's maps sorted by source, so the ones a range overlaps can be found by binary search
+// instead of checking every map
+struct LayerIndex<'a> {
+    maps: Vec<&'a StoDMap>,
+    // The furthest any of maps[..=i] reaches, for finding maps that start before a range
+    // but run into it, even when maps overlap each other
+    reach: Vec<i64>,
+}
 
-        StoDMap {
-            source,
-            dest,
-            length,
+impl LayerIndex<'_> {
+    fn new(maps: &[StoDMap]) -> LayerIndex<'_> {
+        let mut maps: Vec<&StoDMap> = maps.iter().collect();
+        // Stable, so maps with the same source stay in the order they were listed
+        maps.sort_by_key(|m| m.source);
+        let reach = maps
+            .iter()
+            .scan(i64::MIN, |furthest, m| {
+                *furthest = (*furthest).max(m.source + m.length);
+                Some(*furthest)
+            })
+            .collect();
+        LayerIndex { maps, reach }
+    }
+
+    // The maps that `m`'s destination range overlaps, in source order
+    // The same maps, in the same order, as checking each one with StoDMap::overlaps
+    fn overlapping(&self, m: &StoDMap) -> Vec<&StoDMap> {
+        let (start, end) = (m.dest, m.dest + m.length);
+        let starts_inside = self.maps.partition_point(|o| o.source < start);
+        let starts_after = self.maps.partition_point(|o| o.source < end);
+
+        // Maps starting before the range only overlap it if they reach past its start
+        let first = self.reach[..starts_inside].partition_point(|&r| r <= start);
+        let mut out: Vec<&StoDMap> = self.maps[first..starts_inside]
+            .iter()
+            .filter(|o| o.source + o.length > start)
+            .copied()
+            .collect();
+        // A negative length puts the range's end before its start
+        if starts_inside < starts_after {
+            out.extend(&self.maps[starts_inside..starts_after]);
         }
+        out
     }
 }
 
-fn solution(input_str: &str) -> (i64, i64) {
-    let seeds: Vec<i64> = input_str
-        .lines()
-        .next()
-        .expect("")
-        .split_once(':')
-        .expect("")
-        .1
-        .trim()
-        .split(' ')
-        .flat_map(|s| s.parse::<i64>())
-        .collect();
-    let mut seed_mappings_p1: Vec<StoDMap> = seeds
+impl StoDMap {
+    pub fn to_segment(&self) -> Segment {
+        Segment::new(
+            self.source,
+            self.source + self.length,
+            self.dest - self.source,
+        )
+    }
+}
+
+// Same answers as solution(), from the composed map: one binary search per seed,
+// and a scan of the composed segments inside each seed range
+fn composed_solution(almanac: &Almanac) -> Result<(i64, i64), AlmanacError> {
+    let seeds = &almanac.seeds;
+    let conversion = almanac.conversion("seed", "location")?;
+
+    let p1out = seeds
         .iter()
-        .map(|x| StoDMap {
-            source: *x,
-            dest: *x,
-            length: 1,
-        })
-        .collect();
-    let mut seed_mappings_p2: Vec<StoDMap> = seeds
+        .flat_map(|&s| conversion.apply(s))
+        .fold(i64::MAX, min);
+    let p2out = seeds
         .chunks(2)
-        .map(|x| StoDMap {
-            source: x[0],
-            dest: x[0],
-            length: x[1],
-        })
-        .collect();
+        .flat_map(|x| conversion.min_image(&(x[0]..x[0] + x[1])))
+        .fold(i64::MAX, min);
 
-    let mut curr_maps = vec![];
+    Ok((p1out, p2out))
+}
 
-    for line in input_str.lines().skip(3) {
-        if line.is_empty() {
-            continue;
-        }
-        if line.contains("map") {
-            let mut new_mappings = vec![];
-            for m in &seed_mappings_p1 {
-                new_mappings.append(&mut m.squash_many(&curr_maps));
-            }
-            seed_mappings_p1 = new_mappings;
-            new_mappings = vec![];
+// Every seed that ends up somewhere in `locations`, as sorted, merged ranges
+fn seeds_for(almanac: &Almanac, locations: &Range<i64>) -> Result<Vec<Range<i64>>, AlmanacError> {
+    Ok(almanac.conversion("seed", "location")?.preimage(locations))
+}
 
-            for m in &seed_mappings_p2 {
-                new_mappings.append(&mut m.squash_many(&curr_maps));
-            }
-            seed_mappings_p2 = new_mappings;
-            curr_maps = vec![];
-            continue;
-        }
+// Same answers again, but searching from the lowest locations back towards the seeds
+fn reverse_solution(almanac: &Almanac) -> Result<(i64, i64), AlmanacError> {
+    let seeds = &almanac.seeds;
+    let conversion = almanac.conversion("seed", "location")?;
 
-        curr_maps.push(StoDMap::from_string(line.trim()));
+    let seeds_p1 = merge_ranges(seeds.iter().map(|&s| s..s + 1).collect());
+    let seeds_p2 = merge_ranges(seeds.chunks(2).map(|x| x[0]..x[0] + x[1]).collect());
+
+    Ok((
+        conversion.smallest_hit(&seeds_p1).unwrap_or(i64::MAX),
+        conversion.smallest_hit(&seeds_p2).unwrap_or(i64::MAX),
+    ))
+}
+
+// Pushes seed ranges through each layer in turn, letting squash_many split them
+// Each result maps a run of seeds (source) onto a run of locations (dest)
+fn propagate(mut mappings: Vec<StoDMap>, layers: &[&Layer]) -> Vec<StoDMap> {
+    for layer in layers {
+        let index = LayerIndex::new(&layer.maps);
+        let mut new_mappings = vec![];
+        for m in &mappings {
+            new_mappings.append(&mut m.squash_many(&index));
+        }
+        mappings = new_mappings;
     }
+    mappings
+}
 
-    let mut new_mappings = vec![];
-    for m in &seed_mappings_p2 {
-        new_mappings.append(&mut m.squash_many(&curr_maps));
+// The same as propagate(), with the seed ranges split into contiguous runs, one per thread
+// Every range is pushed through on its own, so joining the runs' results back in order
+// gives exactly the sequential output
+fn propagate_parallel(mappings: Vec<StoDMap>, layers: &[&Layer], threads: usize) -> Vec<StoDMap> {
+    if threads <= 1 || mappings.len() < 2 {
+        return propagate(mappings, layers);
     }
-    seed_mappings_p2 = new_mappings;
+
+    let run = mappings.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = mappings
+            .chunks(run)
+            .map(|ranges| scope.spawn(move || propagate(ranges.to_vec(), layers)))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("Propagation thread panicked"))
+            .collect()
+    })
+}
+
+// The part 2 seed ranges, each mapped to where it ends up
+fn seed_mappings_p2(almanac: &Almanac, threads: usize) -> Result<Vec<StoDMap>, AlmanacError> {
+    let seed_mappings: Vec<StoDMap> = almanac
+        .seeds
+        .chunks(2)
+        .map(|x| StoDMap {
+            source: x[0],
+            dest: x[0],
+            length: x[1],
+        })
+        .collect();
+    Ok(propagate_parallel(
+        seed_mappings,
+        &almanac.forward_layers("seed", "location")?,
+        threads,
+    ))
+}
+
+fn solution(almanac: &Almanac, threads: usize) -> Result<(i64, i64), AlmanacError> {
+    let seed_mappings_p1: Vec<StoDMap> = almanac
+        .seeds
+        .iter()
+        .map(|x| StoDMap {
+            source: *x,
+            dest: *x,
+            length: 1,
+        })
+        .collect();
+    let seed_mappings_p1 = propagate_parallel(
+        seed_mappings_p1,
+        &almanac.forward_layers("seed", "location")?,
+        threads,
+    );
+    let seed_mappings_p2 = seed_mappings_p2(almanac, threads)?;
 
     let p1out = seed_mappings_p1
         .iter()
         .fold(i64::MAX, |m, x| min(m, x.dest));
+    // An empty seed range still comes out with a destination, but no seed lands there
     let p2out = seed_mappings_p2
         .iter()
+        .filter(|x| x.length > 0)
         .fold(i64::MAX, |m, x| min(m, x.dest));
 
-    (p1out, p2out)
+    Ok((p1out, p2out))
 }
 
 fn main() {
+    // Usage: day_05 [--composed | --reverse] [--threads n] [--strict]
+    //        day_05 --validate
+    //        day_05 --path from to
+    //        day_05 --queries path [--from category] [--to category]
+    //        day_05 --seeds-for start length
+    //        day_05 [--at-most t] [--landing a b] [--smallest k]
+    //        day_05 --print [--canonical] [--single-layer [--from category] [--to category]]
+    //        day_05 --svg path
+    //        day_05 --generate seed-ranges entries [--seed n]
+    //        day_05 --bench-index
+    // The almanac is checked before anything else; problems are printed to stderr and
+    // errors stop the run, as do warnings with --strict
+    // --threads splits the seed ranges across threads, defaulting to one per core
+    // --queries reads one query per line: a value, or "start length" for a range of values,
+    // and prints what it converts to, or the smallest result in the range
+    // --seeds-for prints the seed ranges that land in the given range of locations
+    // --at-most, --landing and --smallest ask about the part 2 seeds: which reach a location
+    // no higher than t, how many land in a..b, and the k lowest locations reached along
+    // with the seeds that reach them
+    // --print writes the almanac back out; --canonical tidies each layer first, and
+    // --single-layer replaces all the layers with one composed layer
+    // --svg draws how the part 2 seed ranges split and move through each layer
+    // --generate writes a random almanac to stdout instead of solving
+    // --bench-index times finding overlaps with LayerIndex against a linear scan
     let file_path = "input.txt";
 
+    let mut composed = false;
+    let mut reverse = false;
+    let mut queries_path = None;
+    let mut from = String::from("seed");
+    let mut to = String::from("location");
+    let mut show_path = false;
+    let mut locations = None;
+    let mut validate_only = false;
+    let mut strict = false;
+    let mut print = false;
+    let mut canonical = false;
+    let mut single_layer = false;
+    let mut svg_path = None;
+    let mut at_most = None;
+    let mut landing = None;
+    let mut smallest = None;
+    let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut generate = None;
+    let mut seed = 1;
+    let mut bench = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--composed" => composed = true,
+            "--reverse" => reverse = true,
+            "--seeds-for" => {
+                let mut num = || {
+                    args.next()
+                        .and_then(|n| n.parse::<i64>().ok())
+                        .expect("--seeds-for expects a start and a length")
+                };
+                let start = num();
+                locations = Some(start..start + num());
+            }
+            "--queries" => queries_path = Some(args.next().expect("--queries expects a path")),
+            "--from" => from = args.next().expect("--from expects a category"),
+            "--to" => to = args.next().expect("--to expects a category"),
+            "--path" => {
+                show_path = true;
+                from = args.next().expect("--path expects two categories");
+                to = args.next().expect("--path expects two categories");
+            }
+            "--validate" => validate_only = true,
+            "--strict" => strict = true,
+            "--print" => print = true,
+            "--canonical" => canonical = true,
+            "--single-layer" => single_layer = true,
+            "--at-most" => {
+                at_most = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<i64>().ok())
+                        .expect("--at-most expects a location"),
+                );
+            }
+            "--landing" => {
+                let mut num = || {
+                    args.next()
+                        .and_then(|n| n.parse::<i64>().ok())
+                        .expect("--landing expects a start and an end location")
+                };
+                landing = Some(num()..num());
+            }
+            "--smallest" => {
+                smallest = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .expect("--smallest expects a number of locations"),
+                );
+            }
+            "--threads" => {
+                threads = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--threads expects a number of threads");
+            }
+            "--generate" => {
+                let mut num = || {
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .expect("--generate expects a number of seed ranges and of entries")
+                };
+                generate = Some((num(), num()));
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--seed expects a number");
+            }
+            "--bench-index" => bench = true,
+            "--svg" => svg_path = Some(args.next().expect("--svg expects an output path")),
+            x => panic!("Unexpected argument {}", x),
+        }
+    }
+
+    if let Some((seed_ranges, entries)) = generate {
+        print!("{}", generate_almanac(seed_ranges, entries, seed));
+        return;
+    }
+
     let input_str = fs::read_to_string(file_path).expect("Should have been able to read the file");
+    let almanac = Almanac::parse(&input_str).unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+
+    let report = validate(&almanac);
+    if validate_only {
+        for issue in &report.issues {
+            println!("{}", issue);
+        }
+        for summary in &report.summaries {
+            println!("{}", summary);
+        }
+        return;
+    }
+    for issue in &report.issues {
+        eprintln!("{}", issue);
+    }
+    if report.has_errors() || (strict && report.has_warnings()) {
+        panic!("Invalid almanac: {} problems found", report.issues.len());
+    }
+
+    if bench {
+        let (indexed, scanned) =
+            bench_index(&almanac).unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        println!("LayerIndex: {:.2?}", indexed);
+        println!("Linear scan: {:.2?}", scanned);
+        return;
+    }
+
+    if at_most.is_some() || landing.is_some() || smallest.is_some() {
+        let mappings = seed_mappings_p2(&almanac, threads)
+            .unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        if let Some(t) = at_most {
+            for seeds in seeds_at_most(&mappings, t) {
+                println!("{} {}", seeds.start, seeds.end - seeds.start);
+            }
+        }
+        if let Some(locations) = landing {
+            println!("{}", count_landing(&mappings, &locations));
+        }
+        if let Some(k) = smallest {
+            for (location, seeds) in smallest_locations(&mappings, k) {
+                let seeds: Vec<String> = seeds
+                    .iter()
+                    .map(|r| format!("{} {}", r.start, r.end - r.start))
+                    .collect();
+                println!("{}: {}", location, seeds.join(", "));
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = svg_path {
+        let svg = render_svg(&almanac).unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        fs::write(path, svg).expect("Should have been able to write the file");
+        return;
+    }
+
+    if print {
+        let mut out = almanac;
+        if single_layer {
+            out = out
+                .composed(&from, &to)
+                .unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        }
+        if canonical {
+            out = out.canonical();
+        }
+        print!("{}", out.to_text());
+        return;
+    }
+
+    if show_path {
+        let steps = almanac
+            .route(&from, &to)
+            .unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        println!("{}", almanac.describe(&from, &steps));
+        return;
+    }
+
+    if let Some(locations) = locations {
+        for seeds in
+            seeds_for(&almanac, &locations).unwrap_or_else(|e| panic!("Invalid almanac: {}", e))
+        {
+            println!("{} {}", seeds.start, seeds.end - seeds.start);
+        }
+        return;
+    }
+
+    if let Some(path) = queries_path {
+        let conversion = almanac
+            .conversion(&from, &to)
+            .unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
+        let queries = fs::read_to_string(path).expect("Should have been able to read the file");
+        for query in queries.lines() {
+            let nums: Vec<i64> = query
+                .split_whitespace()
+                .map(|s| s.parse().expect("Queries should be numbers"))
+                .collect();
+            let result = match nums[..] {
+                [value] => conversion.apply(value),
+                [start, length] => conversion.min_image(&(start..start + length)),
+                [] => continue,
+                _ => panic!("Expected a value or \"start length\", found {}", query),
+            };
+            match result {
+                Some(x) => println!("{}", x),
+                None => println!("-"),
+            }
+        }
+        return;
+    }
 
     use std::time::Instant;
     let now = Instant::now();
     let n = 1;
     for _ in 0..n {
-        let (p1, p2) = solution(&input_str);
+        let result = if composed {
+            composed_solution(&almanac)
+        } else if reverse {
+            reverse_solution(&almanac)
+        } else {
+            solution(&almanac, threads)
+        };
+        let (p1, p2) = result.unwrap_or_else(|e| panic!("Invalid almanac: {}", e));
         println!("{p1}");
         println!("{p2}");
     }
@@ -224,89 +561,87 @@ fn main() {
     println!("Elapsed for {} iterations: {:.2?}", n, elapsed);
 }
 
-fn old_solution(input_str: &str) -> (i64, i64) {
-    let seeds: Vec<i64> = input_str
-        .lines()
-        .next()
-        .expect("")
-        .split_once(':')
-        .expect("")
-        .1
-        .trim()
-        .split(' ')
-        .flat_map(|s| s.parse::<i64>())
-        .collect();
-    let seeds_p2: Vec<Range<i64>> = seeds
-        .chunks(2)
-        .map(|x| Range {
-            start: x[0],
-            end: x[1] + x[0],
-        })
-        .collect();
-
-    let mut reverse_maps: Vec<HashMap<Range<i64>, i64>> = vec![];
-    let mut curr_rev_map: HashMap<Range<i64>, i64> = HashMap::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate::Rng;
 
-    for line in input_str.lines().skip(3) {
-        if line.contains("map") {
-            reverse_maps.push(curr_rev_map);
-            curr_rev_map = HashMap::new();
-            continue;
-        }
-
-        let range_vec: Vec<&str> = line.split(' ').filter(|s| !s.is_empty()).collect();
-        if range_vec.is_empty() {
-            continue;
-        }
+    // Only the last layer moves anything the seeds reach: it sends seed 20 to location 0
+    // Stopping one layer short leaves seed 1 as the part 1 answer
+    const LAST_LAYER: &str = "seeds: 10 1 20 1
 
-        assert_eq!(range_vec.len(), 3);
+seed-to-humidity map:
+50 60 5
 
-        let dest_start: i64 = range_vec[0].parse().expect("");
-        let source_start: i64 = range_vec[1].parse().expect("");
-        let length: i64 = range_vec[2].parse().expect("");
+humidity-to-location map:
+0 20 1
+";
 
-        curr_rev_map.insert(
-            Range {
-                start: dest_start,
-                end: dest_start + length,
-            },
-            source_start - dest_start,
-        );
+    #[test]
+    fn part_1_goes_through_the_last_layer() {
+        let almanac = Almanac::parse(LAST_LAYER).expect("valid almanac");
+        assert_eq!(solution(&almanac, 1), Ok((0, 0)));
+        assert_eq!(composed_solution(&almanac), Ok((0, 0)));
     }
 
-    reverse_maps.push(curr_rev_map);
-
-    reverse_maps.reverse();
-    let mut min_loc = 0;
-    let mut min_loc1 = -1;
-    let mut min_loc2 = -1;
-    loop {
-        let mut val = min_loc;
-        let mut trans = vec![];
-        for m in &reverse_maps {
-            for r in m.keys() {
-                if r.contains(&val) {
-                    val += m[r];
-                    break;
-                }
+    #[test]
+    fn parallel_propagation_matches_sequential() {
+        for seed in 1..6 {
+            let almanac =
+                Almanac::parse(&generate_almanac(40, 200, seed)).expect("generated almanacs parse");
+            let layers = almanac.forward_layers("seed", "location").expect("a route");
+            let ranges: Vec<StoDMap> = almanac
+                .seeds
+                .chunks(2)
+                .map(|x| StoDMap {
+                    source: x[0],
+                    dest: x[0],
+                    length: x[1],
+                })
+                .collect();
+            let expected = propagate(ranges.clone(), &layers);
+            // Including more threads than ranges, and counts that don't divide them evenly
+            for threads in [0, 1, 2, 3, 7, 40, 64] {
+                assert_eq!(
+                    propagate_parallel(ranges.clone(), &layers, threads),
+                    expected,
+                    "seed {} with {} threads",
+                    seed,
+                    threads
+                );
             }
-            trans.push(val);
-        }
-        if seeds.contains(&val) && min_loc1 < 0 {
-            min_loc1 = min_loc;
-            println!("min_loc1 = {}, transitions: {:?}", min_loc1, trans);
         }
-        for r in &seeds_p2 {
-            if r.contains(&val) && min_loc2 < 0 {
-                min_loc2 = min_loc;
-                println!("min_loc2 = {}, transitions: {:?}", min_loc2, trans);
+    }
+
+    #[test]
+    fn layer_index_finds_the_same_overlaps_as_a_scan() {
+        let mut rng = Rng::new(21);
+        let mut next = |n: i64| rng.below(n as u64) as i64;
+        for _ in 0..3000 {
+            // Short maps packed close together, so they overlap each other, share sources
+            // and are sometimes empty
+            let maps: Vec<StoDMap> = (0..next(12))
+                .map(|_| StoDMap {
+                    source: next(60) - 20,
+                    dest: next(60),
+                    length: next(12),
+                })
+                .collect();
+            let index = LayerIndex::new(&maps);
+            for _ in 0..20 {
+                let m = StoDMap {
+                    source: 0,
+                    dest: next(80) - 30,
+                    length: next(15) - 2,
+                };
+                assert_eq!(
+                    index.overlapping(&m),
+                    bench::scan_overlapping(&maps, &m),
+                    "{:?} against {:?}",
+                    m,
+                    maps
+                );
             }
         }
-        if min_loc1 >= 0 && min_loc2 >= 0 {
-            break;
-        }
-        min_loc += 1;
     }
-
-    (min_loc1, min_loc2)
 }
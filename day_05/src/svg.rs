@@ -0,0 +1,283 @@
+use std::fmt::Write;
+
+use crate::{
+    almanac::{Almanac, AlmanacError},
+    LayerIndex, StoDMap,
+};
+
+const COLUMN_GAP: f64 = 180.0;
+const COLUMN_WIDTH: f64 = 24.0;
+const MARGIN: f64 = 60.0;
+const HEIGHT: f64 = 800.0;
+
+// One piece of a seed range between two columns: where it was before a layer and after
+struct Band {
+    range: usize,
+    before: i64,
+    after: i64,
+    length: i64,
+}
+
+// Maps almanac values onto the page, smallest at the top
+struct Scale {
+    lo: f64,
+    hi: f64,
+}
+
+impl Scale {
+    fn y(&self, value: i64) -> f64 {
+        MARGIN + (value as f64 - self.lo) / (self.hi - self.lo) * HEIGHT
+    }
+
+    // Keeps slivers visible even when the values are spread over billions
+    fn height(&self, start: i64, length: i64) -> f64 {
+        (self.y(start + length) - self.y(start)).max(1.0)
+    }
+}
+
+// Draws the seed -> location layers as columns of intervals, with a band for every piece
+// of the part 2 seed ranges as squash_many splits and shifts them from column to column
+// The seed that reaches the smallest location is traced in red
+pub fn render_svg(almanac: &Almanac) -> Result<String, AlmanacError> {
+    let layers = almanac.forward_layers("seed", "location")?;
+
+    // The same propagation as solution(), remembering which range each piece came from
+    let mut pieces: Vec<(usize, StoDMap)> = almanac
+        .seeds
+        .chunks(2)
+        .enumerate()
+        .map(|(range, x)| {
+            let m = StoDMap {
+                source: x[0],
+                dest: x[0],
+                length: x[1],
+            };
+            (range, m)
+        })
+        .collect();
+    let mut columns = vec![pieces.clone()];
+    let mut bands: Vec<Vec<Band>> = vec![];
+    for layer in &layers {
+        let mut next = vec![];
+        let mut layer_bands = vec![];
+        let index = LayerIndex::new(&layer.maps);
+        for (range, m) in &pieces {
+            for out in m.squash_many(&index) {
+                layer_bands.push(Band {
+                    range: *range,
+                    before: m.dest + (out.source - m.source),
+                    after: out.dest,
+                    length: out.length,
+                });
+                next.push((*range, out));
+            }
+        }
+        pieces = next;
+        columns.push(pieces.clone());
+        bands.push(layer_bands);
+    }
+
+    // The winning seed, and where it sits in every column
+    // Empty seed ranges still have a destination, but no seed lands there, as in solution()
+    let winner = pieces
+        .iter()
+        .filter(|(_, m)| m.length > 0)
+        .min_by_key(|(_, m)| m.dest)
+        .map(|(_, m)| m.source);
+    let trace: Vec<i64> = match winner {
+        Some(seed) => columns
+            .iter()
+            .filter_map(|column| {
+                column
+                    .iter()
+                    .find(|(_, m)| (m.source..m.source + m.length).contains(&seed))
+                    .map(|(_, m)| m.dest + (seed - m.source))
+            })
+            .collect(),
+        None => vec![],
+    };
+
+    let mut values: Vec<i64> = vec![];
+    for column in &columns {
+        for (_, m) in column {
+            values.extend([m.dest, m.dest + m.length]);
+        }
+    }
+    for layer in &layers {
+        for m in &layer.maps {
+            values.extend([m.source, m.source + m.length]);
+        }
+    }
+    let lo = values.iter().min().copied().unwrap_or(0) as f64;
+    let hi = values.iter().max().copied().unwrap_or(1) as f64;
+    let scale = Scale {
+        lo,
+        hi: if hi > lo { hi } else { lo + 1.0 },
+    };
+
+    let x = |column: usize| MARGIN + column as f64 * COLUMN_GAP;
+    let width = x(layers.len()) + COLUMN_WIDTH + 2.0 * MARGIN;
+    let ranges = almanac.seeds.len() / 2;
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" font-family=\"monospace\" font-size=\"12\">",
+        width,
+        HEIGHT + 2.0 * MARGIN
+    )
+    .expect("");
+    out.push_str(
+        "<style>
+.entry { fill: #ccc; stroke: #888; stroke-width: 0.5; }
+.column { fill: none; stroke: #444; }
+.band { fill-opacity: 0.45; stroke-width: 0.5; }
+.winner { fill: none; stroke: #d00; stroke-width: 2; }
+</style>
+",
+    );
+
+    // Column labels and the value range at the top and bottom
+    let mut names = vec!["seed"];
+    names.extend(layers.iter().map(|l| l.to.as_str()));
+    for (column, name) in names.iter().enumerate() {
+        writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x(column) + COLUMN_WIDTH / 2.0,
+            MARGIN - 20.0,
+            escape(name)
+        )
+        .expect("");
+        writeln!(
+            out,
+            "<rect class=\"column\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"/>",
+            x(column),
+            MARGIN,
+            COLUMN_WIDTH,
+            HEIGHT
+        )
+        .expect("");
+    }
+    writeln!(
+        out,
+        "<text x=\"4\" y=\"{:.1}\">{}</text>\n<text x=\"4\" y=\"{:.1}\">{}</text>",
+        MARGIN - 4.0,
+        scale.lo,
+        MARGIN + HEIGHT + 14.0,
+        scale.hi
+    )
+    .expect("");
+
+    // Each layer's entries on the column they map from
+    for (column, layer) in layers.iter().enumerate() {
+        for m in &layer.maps {
+            writeln!(
+                out,
+                "<rect class=\"entry\" x=\"{:.1}\" y=\"{:.2}\" width=\"{:.1}\" height=\"{:.2}\"><title>{} {} {}</title></rect>",
+                x(column),
+                scale.y(m.source),
+                COLUMN_WIDTH,
+                scale.height(m.source, m.length),
+                m.dest,
+                m.source,
+                m.length
+            )
+            .expect("");
+        }
+    }
+
+    // Bands from the right edge of one column to the left edge of the next
+    for (column, layer_bands) in bands.iter().enumerate() {
+        let (x0, x1) = (x(column) + COLUMN_WIDTH, x(column + 1));
+        for b in layer_bands {
+            let hue = 360 * b.range / ranges.max(1);
+            let (y0, y1) = (scale.y(b.before), scale.y(b.after));
+            let (h0, h1) = (
+                scale.height(b.before, b.length),
+                scale.height(b.after, b.length),
+            );
+            writeln!(
+                out,
+                "<polygon class=\"band\" fill=\"hsl({}, 70%, 50%)\" stroke=\"hsl({}, 70%, 35%)\" points=\"{:.1},{:.2} {:.1},{:.2} {:.1},{:.2} {:.1},{:.2}\"><title>{}..{} -> {}..{}</title></polygon>",
+                hue,
+                hue,
+                x0,
+                y0,
+                x1,
+                y1,
+                x1,
+                y1 + h1,
+                x0,
+                y0 + h0,
+                b.before,
+                b.before + b.length,
+                b.after,
+                b.after + b.length
+            )
+            .expect("");
+        }
+    }
+
+    if let (Some(seed), Some(&location)) = (winner, trace.last()) {
+        let points: Vec<String> = trace
+            .iter()
+            .enumerate()
+            .flat_map(|(column, &value)| {
+                let y = scale.y(value);
+                [
+                    format!("{:.1},{:.2}", x(column), y),
+                    format!("{:.1},{:.2}", x(column) + COLUMN_WIDTH, y),
+                ]
+            })
+            .collect();
+        writeln!(
+            out,
+            "<polyline class=\"winner\" points=\"{}\"><title>seed {} -> location {}</title></polyline>",
+            points.join(" "),
+            seed,
+            location
+        )
+        .expect("");
+        writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{:.2}\" fill=\"#d00\">{}</text>",
+            x(layers.len()) + COLUMN_WIDTH + 4.0,
+            scale.y(location) + 4.0,
+            location
+        )
+        .expect("");
+    }
+
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_seed_ranges_never_win() {
+        let almanac = Almanac::parse(
+            "seeds: 5 0 10 3
+
+seed-to-location map:
+100 11 2
+",
+        )
+        .expect("valid almanac");
+        let svg = render_svg(&almanac).expect("a route");
+        assert!(
+            svg.contains("<title>seed 10 -> location 10</title>"),
+            "{}",
+            svg
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::{cmp::max, cmp::min, ops::Range};
+
+use crate::{interval_map::merge_ranges, StoDMap};
+
+// Queries over seed -> location mappings, as built by squash_many
+// Every answer is worked out a whole mapping at a time, never a seed at a time
+
+// The seeds of each mapping that land in `locations`
+fn seeds_landing(mappings: &[StoDMap], locations: &Range<i64>) -> Vec<Range<i64>> {
+    let found = mappings
+        .iter()
+        .filter_map(|m| {
+            let start = max(m.dest, locations.start);
+            let end = min(m.dest + m.length, locations.end);
+            if start < end {
+                let shift = m.source - m.dest;
+                Some(start + shift..end + shift)
+            } else {
+                None
+            }
+        })
+        .collect();
+    merge_ranges(found)
+}
+
+// Every seed whose location is at most `threshold`, as sorted, merged ranges
+pub fn seeds_at_most(mappings: &[StoDMap], threshold: i64) -> Vec<Range<i64>> {
+    seeds_landing(mappings, &(i64::MIN..threshold.saturating_add(1)))
+}
+
+// How many distinct seeds end up in `locations`
+// Seed ranges that overlap in the input are only counted once
+pub fn count_landing(mappings: &[StoDMap], locations: &Range<i64>) -> i128 {
+    seeds_landing(mappings, locations)
+        .iter()
+        .map(|r| r.end as i128 - r.start as i128)
+        .sum()
+}
+
+// The `k` smallest locations any seed reaches, each with the seeds that reach it
+pub fn smallest_locations(mappings: &[StoDMap], k: usize) -> Vec<(i64, Vec<Range<i64>>)> {
+    // Sweeps upwards through the locations, keeping the mappings that cover the current one
+    // and jumping straight to the next mapping's start whenever none do
+    let mut by_dest: Vec<&StoDMap> = mappings.iter().filter(|m| m.length > 0).collect();
+    by_dest.sort_by_key(|m| m.dest);
+
+    let mut out = vec![];
+    let mut covering: Vec<&StoDMap> = vec![];
+    let mut next = 0;
+    let mut location = i64::MIN;
+    while out.len() < k {
+        if covering.is_empty() {
+            match by_dest.get(next) {
+                Some(m) => location = m.dest,
+                None => break,
+            }
+        }
+        while next < by_dest.len() && by_dest[next].dest <= location {
+            covering.push(by_dest[next]);
+            next += 1;
+        }
+        let seeds = covering
+            .iter()
+            .map(|m| {
+                let seed = location + (m.source - m.dest);
+                seed..seed + 1
+            })
+            .collect();
+        out.push((location, merge_ranges(seeds)));
+        location += 1;
+        covering.retain(|m| m.dest + m.length > location);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        almanac::{Almanac, Layer},
+        generate::Rng,
+        seed_mappings_p2,
+    };
+
+    // Seed ranges that overlap each other or are empty, through two layers whose entries
+    // overlap before being put in canonical form, the way the puzzle input lays them out
+    fn random_almanac(rng: &mut Rng) -> Almanac {
+        let mut next = |n: i64| rng.below(n as u64) as i64;
+        let categories = ["seed", "soil", "location"];
+        let layers = categories
+            .windows(2)
+            .map(|names| {
+                Layer {
+                    from: names[0].to_string(),
+                    to: names[1].to_string(),
+                    maps: (0..next(5))
+                        .map(|_| StoDMap {
+                            source: next(40),
+                            dest: next(40),
+                            length: next(10),
+                        })
+                        .collect(),
+                }
+                .canonical()
+            })
+            .collect();
+        Almanac {
+            seeds: (0..next(5)).flat_map(|_| [next(40), next(8)]).collect(),
+            layers,
+        }
+    }
+
+    // Every seed with the location it reaches, one seed at a time
+    fn seed_locations(almanac: &Almanac) -> Vec<(i64, i64)> {
+        let maps: Vec<_> = almanac.layers.iter().map(|l| l.to_map()).collect();
+        let mut found: Vec<(i64, i64)> = almanac
+            .seeds
+            .chunks(2)
+            .flat_map(|x| x[0]..x[0] + x[1])
+            .map(|seed| {
+                let location = maps
+                    .iter()
+                    .fold(seed, |x, map| map.apply(x).expect("layers are total"));
+                (seed, location)
+            })
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    fn points(ranges: &[Range<i64>]) -> Vec<i64> {
+        ranges.iter().flat_map(|r| r.clone()).collect()
+    }
+
+    #[test]
+    fn seeds_at_most_matches_brute_force() {
+        let mut rng = Rng::new(31);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng);
+            let mappings = seed_mappings_p2(&almanac, 1).expect("a route");
+            let found = seed_locations(&almanac);
+            for threshold in -1..60 {
+                let expected: Vec<i64> = found
+                    .iter()
+                    .filter(|&&(_, location)| location <= threshold)
+                    .map(|&(seed, _)| seed)
+                    .collect();
+                assert_eq!(
+                    points(&seeds_at_most(&mappings, threshold)),
+                    expected,
+                    "{} at {}",
+                    almanac.to_text(),
+                    threshold
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn count_landing_matches_brute_force() {
+        let mut rng = Rng::new(32);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng);
+            let mappings = seed_mappings_p2(&almanac, 1).expect("a route");
+            let found = seed_locations(&almanac);
+            for start in (-5..55).step_by(5) {
+                for length in [0, 1, 7, 30] {
+                    let locations = start..start + length;
+                    let expected = found
+                        .iter()
+                        .filter(|(_, location)| locations.contains(location))
+                        .count() as i128;
+                    assert_eq!(
+                        count_landing(&mappings, &locations),
+                        expected,
+                        "{} into {:?}",
+                        almanac.to_text(),
+                        locations
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smallest_locations_matches_brute_force() {
+        let mut rng = Rng::new(33);
+        for _ in 0..500 {
+            let almanac = random_almanac(&mut rng);
+            let mappings = seed_mappings_p2(&almanac, 1).expect("a route");
+            let mut by_location: Vec<(i64, i64)> = seed_locations(&almanac)
+                .into_iter()
+                .map(|(seed, location)| (location, seed))
+                .collect();
+            by_location.sort();
+            let mut expected: Vec<(i64, Vec<i64>)> = vec![];
+            for (location, seed) in by_location {
+                match expected.last_mut() {
+                    Some((last, seeds)) if *last == location => seeds.push(seed),
+                    _ => expected.push((location, vec![seed])),
+                }
+            }
+
+            for k in [0, 1, 3, 100] {
+                let smallest: Vec<(i64, Vec<i64>)> = smallest_locations(&mappings, k)
+                    .into_iter()
+                    .map(|(location, seeds)| (location, points(&seeds)))
+                    .collect();
+                let expected = &expected[..k.min(expected.len())];
+                assert_eq!(smallest, expected, "{} for {}", almanac.to_text(), k);
+            }
+        }
+    }
+}
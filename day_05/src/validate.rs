@@ -0,0 +1,249 @@
+use std::{fmt, ops::Range};
+
+use crate::{
+    almanac::{Almanac, Layer},
+    interval_map::{IntervalMap, Segment},
+};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Problem {
+    // Two entries claim some of the same source values, so which one applies depends on
+    // the order they are listed in
+    Overlap { first: usize, second: usize },
+    // An entry that maps nothing
+    Empty { entry: usize },
+    NegativeLength { entry: usize },
+    // source + length, destination + length or the shift from source to destination
+    // doesn't fit in an i64
+    Overflow { entry: usize },
+}
+
+// A problem with one layer; entries are numbered from 1 in the order they are listed
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Issue {
+    pub layer: String,
+    pub problem: Problem,
+}
+
+impl Issue {
+    // Errors stop the almanac being solved at all; warnings only do with --strict
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.problem,
+            Problem::NegativeLength { .. } | Problem::Overflow { .. }
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = if self.is_error() { "error" } else { "warning" };
+        write!(f, "{}: {} map: ", level, self.layer)?;
+        match self.problem {
+            Problem::Overlap { first, second } => write!(
+                f,
+                "entries {} and {} have overlapping source ranges",
+                first, second
+            ),
+            Problem::Empty { entry } => write!(f, "entry {} has zero length", entry),
+            Problem::NegativeLength { entry } => {
+                write!(f, "entry {} has a negative length", entry)
+            }
+            Problem::Overflow { entry } => {
+                write!(
+                    f,
+                    "entry {} has values or a shift too big for an i64",
+                    entry
+                )
+            }
+        }
+    }
+}
+
+// How much of a layer actually moves values, between its lowest and highest source
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct LayerSummary {
+    pub layer: String,
+    pub entries: usize,
+    // None if the layer has no usable entries
+    pub span: Option<Range<i64>>,
+    // Values in the span sent somewhere else, and values left where they are, either by
+    // falling between entries or by an entry with the same source and destination
+    pub shifted: i128,
+    pub identity: i128,
+}
+
+impl fmt::Display for LayerSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} map: {} entries", self.layer, self.entries)?;
+        match &self.span {
+            Some(span) => {
+                let total = self.shifted + self.identity;
+                write!(
+                    f,
+                    ", sources {}..{}, {} shifted, {} identity ({:.1}%)",
+                    span.start,
+                    span.end,
+                    self.shifted,
+                    self.identity,
+                    100.0 * self.identity as f64 / total as f64
+                )
+            }
+            None => write!(f, ", all identity"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    pub summaries: Vec<LayerSummary>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.is_error())
+    }
+
+    pub fn has_warnings(&self) -> bool {
+        self.issues.iter().any(|i| !i.is_error())
+    }
+}
+
+pub fn validate(almanac: &Almanac) -> Report {
+    let mut issues = vec![];
+    let mut summaries = vec![];
+    for layer in &almanac.layers {
+        let name = format!("{}-to-{}", layer.from, layer.to);
+        let (problems, usable) = check_layer(layer);
+        issues.extend(problems.into_iter().map(|problem| Issue {
+            layer: name.clone(),
+            problem,
+        }));
+        summaries.push(summarise(name, layer.maps.len(), &usable));
+    }
+    Report { issues, summaries }
+}
+
+// The layer's problems, and the segments of the entries that are safe to use
+fn check_layer(layer: &Layer) -> (Vec<Problem>, Vec<Segment>) {
+    let mut problems = vec![];
+    let mut usable: Vec<(usize, Segment)> = vec![];
+    for (idx, m) in layer.maps.iter().enumerate() {
+        let entry = idx + 1;
+        if m.length == 0 {
+            problems.push(Problem::Empty { entry });
+        } else if m.length < 0 {
+            problems.push(Problem::NegativeLength { entry });
+        } else if m.source.checked_add(m.length).is_none()
+            || m.dest.checked_add(m.length).is_none()
+            || m.dest.checked_sub(m.source).is_none()
+        {
+            problems.push(Problem::Overflow { entry });
+        } else {
+            usable.push((entry, m.to_segment()));
+        }
+    }
+
+    // Sweeping in source order, each entry is checked against whichever earlier one
+    // reaches furthest, so every overlapping entry is reported at least once
+    let mut by_source = usable.clone();
+    by_source.sort_by_key(|(entry, s)| (s.start, *entry));
+    let mut furthest: Option<(usize, i64)> = None;
+    let mut overlaps = vec![];
+    for &(entry, s) in &by_source {
+        match furthest {
+            Some((other, end)) if end > s.start => {
+                overlaps.push((other.min(entry), other.max(entry)));
+                if s.end > end {
+                    furthest = Some((entry, s.end));
+                }
+            }
+            _ => furthest = Some((entry, s.end)),
+        }
+    }
+    overlaps.sort();
+    problems.extend(
+        overlaps
+            .into_iter()
+            .map(|(first, second)| Problem::Overlap { first, second }),
+    );
+
+    (problems, usable.into_iter().map(|(_, s)| s).collect())
+}
+
+fn summarise(layer: String, entries: usize, usable: &[Segment]) -> LayerSummary {
+    let span = usable
+        .iter()
+        .map(|s| s.start)
+        .min()
+        .zip(usable.iter().map(|s| s.end).max())
+        .map(|(start, end)| start..end);
+
+    let (shifted, identity) = match &span {
+        Some(span) => {
+            // Earlier entries win where they overlap, as in Layer::to_map
+            let map = IntervalMap::from_layered(usable);
+            let shifted: i128 = map
+                .pieces(span)
+                .filter(|s| s.offset != 0)
+                .map(|s| s.end as i128 - s.start as i128)
+                .sum();
+            (shifted, span.end as i128 - span.start as i128 - shifted)
+        }
+        None => (0, 0),
+    };
+
+    LayerSummary {
+        layer,
+        entries,
+        span,
+        shifted,
+        identity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(entries: &str) -> Vec<Problem> {
+        let almanac = Almanac::parse(&format!("seeds: 1\n\nseed-to-soil map:\n{}", entries))
+            .expect("valid almanac");
+        check_layer(&almanac.layers[0]).0
+    }
+
+    #[test]
+    fn entries_that_overflow_are_errors() {
+        assert_eq!(
+            problems("0 9223372036854775800 10"),
+            vec![Problem::Overflow { entry: 1 }]
+        );
+        assert_eq!(
+            problems("9223372036854775800 0 10"),
+            vec![Problem::Overflow { entry: 1 }]
+        );
+    }
+
+    #[test]
+    fn a_shift_too_big_for_an_i64_is_an_overflow() {
+        assert_eq!(
+            problems("9000000000000000000 -9000000000000000000 1\n-9000000000000000000 9000000000000000000 1"),
+            vec![Problem::Overflow { entry: 1 }, Problem::Overflow { entry: 2 }]
+        );
+        // An error, so main stops before the entry is turned into a segment
+        let almanac = Almanac::parse(
+            "seeds: 1\n\nseed-to-location map:\n9000000000000000000 -9000000000000000000 1\n",
+        )
+        .expect("valid almanac");
+        assert!(validate(&almanac).has_errors());
+    }
+
+    #[test]
+    fn large_but_representable_shifts_are_fine() {
+        assert_eq!(
+            problems("4000000000000000000 -4000000000000000000 1"),
+            vec![]
+        );
+    }
+}